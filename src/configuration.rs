@@ -1,4 +1,4 @@
-use std::path::PathBuf;
+use std::{env, path::{Path, PathBuf}, time::Duration};
 
 use anyhow::{anyhow, Context, Ok, Result};
 use gix_config::File;
@@ -6,10 +6,31 @@ use serde::{Deserialize, Serialize};
 use tracing::info;
 use url::Url;
 
+use crate::{credential_manager::USER_ENVIRONMENT_VARIABLE, synology_api::{normalize_fingerprint, ClientOptions}};
+
+const DEFAULT_SESSION_LIFETIME: Duration = Duration::from_secs(10 * 60);
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Configuration {
+    pub base_path: String,
+    pub ca_bundle: Option<PathBuf>,
+    pub certificate_fingerprint: Option<String>,
+    pub connect_timeout: Option<Duration>,
+    pub download_connections: Option<usize>,
+    pub idle_timeout: Option<Duration>,
     pub nas_url: String,
-    pub path: String
+    pub parallel_download_threshold: Option<u64>,
+    pub path: String,
+    pub proxy: Option<String>,
+    pub request_timeout: Option<Duration>,
+    pub response_timeout: Option<Duration>,
+    pub retry_attempts: Option<u32>,
+    pub retry_deadline: Option<Duration>,
+    pub session_cache: bool,
+    pub session_lifetime: Option<Duration>,
+    pub ssl_verify: bool,
+    pub user: Option<String>,
+    pub verify_upload: bool
 }
 
 impl Configuration {
@@ -43,11 +64,174 @@ impl Configuration {
 
         info!("nas_url: \"{}\", path: \"{}\".", nas_url, path);
 
+        let verify_upload = config.boolean("synology.verifyupload").transpose()?.unwrap_or(false);
+        info!("verify_upload: {}", verify_upload);
+
+        let base_path = Configuration::clean_base_path(
+            config.string("synology.basepath").map(|value| value.to_string()).unwrap_or_default().as_str());
+        info!("base_path: \"{}\".", base_path);
+
+        let connect_timeout = Configuration::get_seconds(&config, "synology.connecttimeout")?;
+        let idle_timeout = Configuration::get_seconds(&config, "synology.idletimeout")?;
+        info!("connect_timeout: {:?}, idle_timeout: {:?}.", connect_timeout, idle_timeout);
+
+        let download_connections = Configuration::get_positive(&config, "synology.downloadconnections")?.map(|value| value as usize);
+        let parallel_download_threshold = Configuration::get_positive(&config, "synology.paralleldownloadthreshold")?;
+        let request_timeout = Configuration::get_seconds(&config, "synology.requesttimeout")?;
+        let response_timeout = Configuration::get_seconds(&config, "synology.responsetimeout")?;
+        info!("request_timeout: {:?}, response_timeout: {:?}.", request_timeout, response_timeout);
+
+        let retry_attempts = Configuration::get_positive(&config, "synology.retryattempts")?.map(|value| value as u32);
+        let retry_deadline = Configuration::get_seconds(&config, "synology.retrydeadline")?;
+        info!("retry_attempts: {:?}, retry_deadline: {:?}.", retry_attempts, retry_deadline);
+
+        let session_cache = config.boolean("synology.sessioncache").transpose()?.unwrap_or(true);
+        let session_lifetime = Configuration::get_seconds(&config, "synology.sessionlifetime")?;
+        info!("session_cache: {}, session_lifetime: {:?}.", session_cache, session_lifetime);
+
+        let proxy = config.string("synology.proxy").map(|value| value.to_string());
+        info!("proxy: {:?}.", proxy);
+
+        let ssl_verify = config.boolean("synology.sslverify").transpose()?.unwrap_or(true);
+        info!("ssl_verify: {}", ssl_verify);
+
+        let ca_bundle = config.path("synology.cabundle")
+            .map(|value| value.interpolate(Default::default()).map(|path| path.to_path_buf()))
+            .transpose()?;
+        let certificate_fingerprint = config.string("synology.certificatefingerprint")
+            .map(|value| normalize_fingerprint(value.to_string().as_str()))
+            .transpose()?;
+        info!("ca_bundle: {:?}, certificate_fingerprint: {:?}.", ca_bundle, certificate_fingerprint);
+
+        info!("download_connections: {:?}, parallel_download_threshold: {:?}.", download_connections, parallel_download_threshold);
+
+        let user = Configuration::get_user(&config);
+        info!("user: {:?}.", user);
+
         Ok(
             Configuration {
+                base_path,
+                ca_bundle,
+                certificate_fingerprint,
+                connect_timeout,
+                download_connections,
+                idle_timeout,
                 nas_url: nas_url.to_string(),
-                path: path.to_string()
+                parallel_download_threshold,
+                path: path.to_string(),
+                proxy,
+                request_timeout,
+                response_timeout,
+                retry_attempts,
+                retry_deadline,
+                session_cache,
+                session_lifetime,
+                ssl_verify,
+                user,
+                verify_upload
             }
         )
     }
+
+    #[tracing::instrument(skip(config))]
+    fn get_seconds(config: &File, key: &str) -> Result<Option<Duration>> {
+        match config.integer(key).transpose()? {
+            Some(seconds) if seconds <= 0 => Err(anyhow!("\"{}\" must be a positive number of seconds.", key)),
+            Some(seconds) => Ok(Some(Duration::from_secs(seconds as u64))),
+            None => Ok(None)
+        }
+    }
+
+    #[tracing::instrument(skip(config))]
+    fn get_positive(config: &File, key: &str) -> Result<Option<u64>> {
+        match config.integer(key).transpose()? {
+            Some(value) if value <= 0 => Err(anyhow!("\"{}\" must be a positive number.", key)),
+            Some(value) => Ok(Some(value as u64)),
+            None => Ok(None)
+        }
+    }
+
+    /// Returns the user to log in as, from the environment, `.lfsconfig` or git config in that order.
+    #[tracing::instrument(skip(config))]
+    fn get_user(config: &File) -> Option<String> {
+        let repository_config = gix_discover::upwards(Path::new("./")).ok()
+            .and_then(|(repository, _)| {
+                let (repository_path, _) = repository.into_repository_and_work_tree_directories();
+
+                File::from_path_no_includes(repository_path.join("config"), gix_config::Source::Local).ok()
+            });
+
+        env::var(USER_ENVIRONMENT_VARIABLE).ok()
+            .or_else(|| config.string("synology.user").map(|value| value.to_string()))
+            .or_else(|| repository_config.and_then(|config| config.string("synology.user").map(|value| value.to_string())))
+            .or_else(|| File::from_globals().ok().and_then(|config| config.string("synology.user").map(|value| value.to_string())))
+            .filter(|user| !user.is_empty())
+    }
+
+    #[tracing::instrument]
+    pub fn clean_base_path(base_path: &str) -> String {
+        let base_path = base_path.trim_matches('/');
+
+        if base_path.is_empty() {
+            "".to_string()
+        }
+        else {
+            format!("/{}", base_path)
+        }
+    }
+
+    #[tracing::instrument]
+    pub fn session_lifetime(&self) -> Duration {
+        // Stay within the 15 minutes after which DSM ends idle sessions by default.
+        self.session_lifetime.unwrap_or(DEFAULT_SESSION_LIFETIME)
+    }
+
+    #[tracing::instrument]
+    pub fn client_options(&self) -> ClientOptions {
+        let mut options = ClientOptions::default();
+
+        if let Some(connect_timeout) = self.connect_timeout {
+            options.connect_timeout = connect_timeout;
+        }
+
+        if let Some(idle_timeout) = self.idle_timeout {
+            options.idle_timeout = idle_timeout;
+        }
+
+        if let Some(download_connections) = self.download_connections {
+            options.download_connections = download_connections;
+        }
+
+        if let Some(parallel_download_threshold) = self.parallel_download_threshold {
+            options.parallel_download_threshold = parallel_download_threshold;
+        }
+
+        if let Some(request_timeout) = self.request_timeout {
+            options.request_timeout = request_timeout;
+        }
+
+        if let Some(response_timeout) = self.response_timeout {
+            options.response_timeout = response_timeout;
+        }
+
+        if let Some(retry_attempts) = self.retry_attempts {
+            options.retry.max_attempts = retry_attempts;
+        }
+
+        if let Some(retry_deadline) = self.retry_deadline {
+            options.retry.deadline = retry_deadline;
+        }
+
+        options.proxy = self.proxy.clone();
+        options.verify_certificate = self.ssl_verify;
+        options.ca_bundle = self.ca_bundle.clone();
+        options.certificate_fingerprint = self.certificate_fingerprint.clone();
+
+        options
+    }
+
+    #[tracing::instrument]
+    pub fn dsm_url(&self) -> String {
+        format!("{}{}", self.nas_url, self.base_path)
+    }
 }
\ No newline at end of file
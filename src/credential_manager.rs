@@ -1,15 +1,27 @@
-use std::{collections::HashMap, fs::create_dir_all};
+use std::{collections::HashMap, env, fmt::{self, Display}, fs::{self, create_dir_all}, io::Write, path::PathBuf, str::FromStr, time::{Duration, SystemTime, UNIX_EPOCH}};
 
-use aes_gcm::{aead::{Aead, OsRng}, AeadCore, Aes256Gcm, Key, KeyInit, Nonce};
+use aes_gcm::{aead::{rand_core::RngCore, Aead, OsRng}, AeadCore, Aes256Gcm, Key, KeyInit, Nonce};
+use argon2::Argon2;
 use anyhow::{anyhow, bail, Context, Result};
 use educe::Educe;
 use keyring::Entry;
-use rusqlite::Connection;
+use rusqlite::{Connection, Transaction, TransactionBehavior};
 use thiserror::Error;
-use tracing::{debug, info};
+use tracing::{debug, info, warn};
 
 use crate::users_dirs::get_config_dir;
 
+pub const USER_ENVIRONMENT_VARIABLE: &str = "GIT_LFS_SYNOLOGY_USER";
+const PASSWORD_ENVIRONMENT_VARIABLE: &str = "GIT_LFS_SYNOLOGY_PASSWORD";
+const TOTP_SECRET_ENVIRONMENT_VARIABLE: &str = "GIT_LFS_SYNOLOGY_TOTP_SECRET";
+const CREDENTIAL_STORE_ENVIRONMENT_VARIABLE: &str = "GIT_LFS_SYNOLOGY_CREDENTIAL_STORE";
+const MASTER_PASSPHRASE_ENVIRONMENT_VARIABLE: &str = "GIT_LFS_SYNOLOGY_MASTER_PASSPHRASE";
+const KEY_FILE_ENVIRONMENT_VARIABLE: &str = "GIT_LFS_SYNOLOGY_KEY_FILE";
+const KEY_FILE_NAME: &str = "machine.key";
+const KEY_LENGTH: usize = 32;
+const SALT_LENGTH: usize = 16;
+const DATABASE_BUSY_TIMEOUT: Duration = Duration::from_secs(30);
+
 #[derive(Error, Debug)]
 enum CredentialError {
     #[error("Sqlite database is not initialized.")]
@@ -24,16 +36,21 @@ enum CredentialError {
 struct DatabaseCredential {
     user: String,
     device_id_encrypted: Option<Vec<u8>>,
-    device_id_nonce: Option<Vec<u8>>
+    device_id_nonce: Option<Vec<u8>>,
+    device_id_salt: Option<Vec<u8>>
 }
 
-#[derive(Educe)]
+#[derive(Clone, Educe)]
 #[educe(Debug)]
 pub struct Credential {
     pub user: String,
     #[educe(Debug(ignore))] // Do not include password in logs.
     pub password: String,
-    pub device_id: Option<String>
+    pub device_id: Option<String>,
+    pub certificate_fingerprint: Option<String>,
+    pub ca_bundle: Option<PathBuf>,
+    #[educe(Debug(ignore))] // Do not include the TOTP secret in logs.
+    pub totp_secret: Option<String>
 }
 
 impl Credential {
@@ -42,26 +59,136 @@ impl Credential {
         Credential {
             user,
             password,
-            device_id: None
+            device_id: None,
+            certificate_fingerprint: None,
+            ca_bundle: None,
+            totp_secret: None
         }
     }
+
+    /// Reads a credential from the environment, for machines without a credential store such as CI containers.
+    #[tracing::instrument]
+    pub fn from_environment() -> Option<Credential> {
+        let user = env::var(USER_ENVIRONMENT_VARIABLE).ok().filter(|user| !user.is_empty())?;
+        let password = env::var(PASSWORD_ENVIRONMENT_VARIABLE).ok()?;
+        info!("Found credential for \"{}\" in the environment.", user);
+
+        let mut credential = Credential::new(user, password);
+        credential.totp_secret = env::var(TOTP_SECRET_ENVIRONMENT_VARIABLE).ok().filter(|secret| !secret.is_empty());
+
+        Some(credential)
+    }
 }
 
+/// A summary of a stored credential that does not include any secrets.
 #[derive(Debug)]
+pub struct StoredCredential {
+    pub url: String,
+    pub user: String,
+    pub is_default: bool,
+    pub has_device_id: bool,
+    pub has_totp_secret: bool,
+    pub last_used: Option<SystemTime>
+}
+
+/// Where passwords are kept. Everything else is always kept in the sqlite database.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CredentialStore {
+    /// The operating system's credential store.
+    Keyring,
+    /// The sqlite database, encrypted with a master passphrase or a machine key file.
+    Database
+}
+
+impl FromStr for CredentialStore {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<CredentialStore> {
+        match value.to_lowercase().as_str() {
+            "keyring" => Ok(CredentialStore::Keyring),
+            "database" => Ok(CredentialStore::Database),
+            _ => bail!("\"{}\" is not a credential store. Use \"keyring\" or \"database\".", value)
+        }
+    }
+}
+
+impl Display for CredentialStore {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CredentialStore::Keyring => write!(formatter, "keyring"),
+            CredentialStore::Database => write!(formatter, "database")
+        }
+    }
+}
+
+#[derive(Educe)]
+#[educe(Debug)]
 pub struct CredentialManager {
     connection: Connection,
-    entry_cache: HashMap<(String, String), Entry>
+    entry_cache: HashMap<(String, String), Entry>,
+    store: CredentialStore,
+    #[educe(Debug(ignore))] // Do not include the database secret in logs.
+    database_secret: Option<Vec<u8>>
 }
 
 impl CredentialManager {
     #[tracing::instrument]
     pub fn new() -> Result<CredentialManager> {
+        CredentialManager::with_store(CredentialManager::select_store()?)
+    }
+
+    #[tracing::instrument]
+    pub fn with_store(store: CredentialStore) -> Result<CredentialManager> {
+        info!("Using the {} credential store.", store);
+
         Ok(CredentialManager {
             connection: CredentialManager::get_connection()?,
-            entry_cache: HashMap::new()
+            entry_cache: HashMap::new(),
+            store,
+            database_secret: None
         })
     }
 
+    #[tracing::instrument]
+    pub fn store(&self) -> CredentialStore {
+        self.store
+    }
+
+    /// Uses the configured store, or the keyring if one is available and the database otherwise.
+    #[tracing::instrument]
+    fn select_store() -> Result<CredentialStore> {
+        let configured = env::var(CREDENTIAL_STORE_ENVIRONMENT_VARIABLE).ok()
+            .or_else(|| gix_config::File::from_globals().ok()
+                .and_then(|config| config.string("synology.credentialstore").map(|value| value.to_string())));
+
+        match configured.as_deref() {
+            Some(store) if !store.eq_ignore_ascii_case("auto") => store.parse(),
+            _ => {
+                if CredentialManager::is_keyring_available() {
+                    Ok(CredentialStore::Keyring)
+                }
+                else {
+                    warn!("No operating system credential store is available, using the database.");
+
+                    Ok(CredentialStore::Database)
+                }
+            }
+        }
+    }
+
+    #[tracing::instrument]
+    fn is_keyring_available() -> bool {
+        // A missing entry means the store answered, while any other error means it could not be reached.
+        match Entry::new("git-lfs-synology", "keyring-probe").and_then(|entry| entry.get_password()) {
+            Ok(_) | Err(keyring::Error::NoEntry) => true,
+            Err(error) => {
+                info!("The operating system credential store is not available: \"{error}\".");
+
+                false
+            }
+        }
+    }
+
     #[tracing::instrument]
     fn clean_url(&self, url: &str) -> String {
         if let Some(url) = url.strip_suffix("/") {
@@ -85,7 +212,12 @@ impl CredentialManager {
         }
 
         debug!("Creating sqlite database connection.");
-        Ok(Connection::open(sqlite_path)?)
+        let connection = Connection::open(sqlite_path)?;
+
+        // Wait for other agents that are writing to the database rather than failing.
+        connection.busy_timeout(DATABASE_BUSY_TIMEOUT)?;
+
+        Ok(connection)
     }
 
     #[tracing::instrument]
@@ -94,12 +226,13 @@ impl CredentialManager {
 
         info!("Selecting rows from user database.");
         let mut stmt: rusqlite::Statement<'_> = database.prepare(
-            "SELECT user, device_id_encrypted, device_id_nonce FROM Credentials WHERE url=:url;")?;
+            "SELECT user, device_id_encrypted, device_id_nonce, device_id_salt FROM Credentials WHERE url=:url;")?;
         let rows: Vec<DatabaseCredential> = stmt.query_map(&[(":url", url)], |row| {
             Ok(DatabaseCredential {
                 user: row.get(0)?,
                 device_id_encrypted: row.get(1)?,
-                device_id_nonce: row.get(2)?
+                device_id_nonce: row.get(2)?,
+                device_id_salt: row.get(3)?
             })
         })?.filter_map(|r| r.ok()).collect::<Vec<DatabaseCredential>>();
 
@@ -140,6 +273,41 @@ impl CredentialManager {
         Ok(())
     }
 
+    /// Adds a salt to each encrypted value. Rows without one still use the padded password as the key and are
+    /// re-encrypted the first time they are read.
+    #[tracing::instrument]
+    fn migrate_to_version_2(&self, connection: &Connection) -> Result<()> {
+        // git-lfs starts several agents at once, so take the write lock before checking whether another agent has
+        // already upgraded the database. The transaction is rolled back if it is dropped without committing.
+        let transaction = Transaction::new_unchecked(connection, TransactionBehavior::Immediate)?;
+
+        if self.get_database_version(&transaction)? >= 2 {
+            info!("The user database was already upgraded.");
+
+            return Ok(());
+        }
+
+        info!("Upgrading the user database to version 2.");
+
+        let mut stmt: rusqlite::Statement<'_> = transaction.prepare(
+            "SELECT name FROM sqlite_master WHERE type='table' AND name='TotpSecrets';")?;
+        let has_totp_secrets = stmt.exists([])?;
+        drop(stmt);
+
+        transaction.execute("ALTER TABLE Credentials ADD COLUMN device_id_salt BLOB", ())?;
+        if has_totp_secrets {
+            transaction.execute("ALTER TABLE TotpSecrets ADD COLUMN secret_salt BLOB", ())?;
+        }
+
+        // Cached sessions are simply dropped, as they would have to be created again anyway.
+        transaction.execute("DROP TABLE IF EXISTS Sessions", ())?;
+        transaction.execute("UPDATE Metadata SET value='2' WHERE key='version'", ())?;
+
+        transaction.commit()?;
+
+        Ok(())
+    }
+
     #[tracing::instrument]
     fn get_database(&self) -> Result<&Connection> {
         info!("Creating Credentials table in user database.");
@@ -158,9 +326,13 @@ impl CredentialManager {
                              ALTER TABLE Credentials ADD COLUMN device_id_nonce BLOB;",
                         )?;
 
-                        self.create_tables(conn)
+                        self.create_tables(conn)?;
+                        self.migrate_to_version_2(conn)
                     }
                     1 => {
+                        self.migrate_to_version_2(conn)
+                    }
+                    2 => {
                         // Up to date. Do Nothing
 
                         Ok(())
@@ -171,12 +343,85 @@ impl CredentialManager {
             Err(err) =>
                 match err {
                     CredentialError::DatabaseNotInitialized => {
-                        self.create_tables(conn)
+                        self.create_tables(conn)?;
+                        self.migrate_to_version_2(conn)
                     },
                     _ => Err(anyhow!(err))
                 }
         }?;
 
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS Passwords (
+                url                     TEXT NOT NULL,
+                user                    TEXT NOT NULL,
+                password_encrypted      BLOB NOT NULL,
+                password_nonce          BLOB NOT NULL,
+                salt                    BLOB NOT NULL,
+                PRIMARY KEY (url, user)
+            )",
+            (), // empty list of parameters.
+        )?;
+
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS Sessions (
+                url                     TEXT NOT NULL,
+                user                    TEXT NOT NULL,
+                sid_encrypted           BLOB NOT NULL,
+                sid_nonce               BLOB NOT NULL,
+                sid_salt                BLOB NOT NULL,
+                expires                 INTEGER NOT NULL,
+                PRIMARY KEY (url, user)
+            )",
+            (), // empty list of parameters.
+        )?;
+
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS TotpSecrets (
+                url                     TEXT NOT NULL,
+                user                    TEXT NOT NULL,
+                secret_encrypted        BLOB NOT NULL,
+                secret_nonce            BLOB NOT NULL,
+                secret_salt             BLOB,
+                PRIMARY KEY (url, user)
+            )",
+            (), // empty list of parameters.
+        )?;
+
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS LastUsed (
+                url                     TEXT NOT NULL,
+                user                    TEXT NOT NULL,
+                last_used               INTEGER NOT NULL,
+                PRIMARY KEY (url, user)
+            )",
+            (), // empty list of parameters.
+        )?;
+
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS DefaultUsers (
+                url                     TEXT PRIMARY KEY,
+                user                    TEXT NOT NULL
+            )",
+            (), // empty list of parameters.
+        )?;
+
+        // Pinned certificates belong to the NAS rather than a user, so they are kept in their own table.
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS CertificateFingerprints (
+                url                     TEXT PRIMARY KEY,
+                fingerprint             TEXT NOT NULL
+            )",
+            (), // empty list of parameters.
+        )?;
+
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS CaBundles (
+                url                     TEXT PRIMARY KEY,
+                path                    TEXT NOT NULL
+            )",
+            (), // empty list of parameters.
+        )?;
+
         Ok(conn)
     }
 
@@ -213,6 +458,258 @@ impl CredentialManager {
         Ok(version.to_owned())
     }
 
+    /// Returns the master passphrase from the environment, or else the machine key file, creating it if needed.
+    #[tracing::instrument]
+    fn get_database_secret(&mut self) -> Result<Vec<u8>> {
+        if let Some(database_secret) = &self.database_secret {
+            return Ok(database_secret.clone());
+        }
+
+        let database_secret = match env::var(MASTER_PASSPHRASE_ENVIRONMENT_VARIABLE) {
+            Ok(passphrase) if !passphrase.is_empty() => {
+                info!("Using the master passphrase from the environment.");
+
+                passphrase.into_bytes()
+            },
+            _ => {
+                let path = match env::var(KEY_FILE_ENVIRONMENT_VARIABLE) {
+                    Ok(path) if !path.is_empty() => path.into(),
+                    _ => get_config_dir()?.join(KEY_FILE_NAME)
+                };
+
+                if !path.exists() {
+                    info!("Creating the machine key file.");
+
+                    let mut key = vec![0u8; KEY_LENGTH];
+                    OsRng.fill_bytes(&mut key);
+
+                    let mut options = fs::OpenOptions::new();
+                    options.write(true).create_new(true);
+                    #[cfg(unix)]
+                    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600); // Only readable by the user.
+
+                    options.open(&path)?.write_all(&key)?;
+                }
+
+                info!("Using the machine key file.");
+                fs::read(&path).with_context(|| format!("Could not read the key file \"{}\".", path.to_string_lossy()))?
+            }
+        };
+
+        self.database_secret = Some(database_secret.clone());
+
+        Ok(database_secret)
+    }
+
+    #[tracing::instrument(skip(secret))]
+    fn derive_key(&self, secret: &[u8], salt: &[u8]) -> Result<[u8; KEY_LENGTH]> {
+        let mut key = [0u8; KEY_LENGTH];
+        Argon2::default()
+            .hash_password_into(secret, salt, &mut key)
+            .map_err(|error| anyhow!("Could not derive the key: {error}"))?;
+
+        Ok(key)
+    }
+
+    #[tracing::instrument]
+    fn get_password(&mut self, url: &str, user: &str) -> Result<String> {
+        match self.store {
+            CredentialStore::Keyring => {
+                info!("Getting password from operating system credential store.");
+                let entry = self.get_entry(url, user)?;
+
+                Ok(entry.get_password()?)
+            },
+            CredentialStore::Database => {
+                info!("Getting password from the database.");
+                let database = self.get_database()?;
+
+                let mut stmt: rusqlite::Statement<'_> = database.prepare(
+                    "SELECT password_encrypted, password_nonce, salt FROM Passwords WHERE url=:url AND user=:user;")?;
+                let (password_encrypted, password_nonce, salt) = stmt
+                    .query_map(&[(":url", url), (":user", user)], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))?
+                    .filter_map(|r| r.ok())
+                    .collect::<Vec<(Vec<u8>, Vec<u8>, Vec<u8>)>>()
+                    .into_iter()
+                    .next()
+                    .context("No password is stored in the database.")?;
+                drop(stmt);
+
+                let database_secret = self.get_database_secret()?;
+                let key = self.derive_key(&database_secret, &salt)?;
+                let cipher = Aes256Gcm::new(&key.into());
+                let plaintext = cipher
+                    .decrypt(&Nonce::from_iter(password_nonce), password_encrypted.as_ref())
+                    .map_err(|_| anyhow!("Could not decrypt the password. Check the master passphrase or key file."))?;
+
+                Ok(String::from_utf8(plaintext)?)
+            }
+        }
+    }
+
+    #[tracing::instrument(skip(password))]
+    fn set_password(&mut self, url: &str, user: &str, password: &str) -> Result<()> {
+        match self.store {
+            CredentialStore::Keyring => {
+                info!("Storing the password into the operating system credential store.");
+                let entry = self.get_entry(url, user)?;
+
+                Ok(entry.set_password(password)?)
+            },
+            CredentialStore::Database => {
+                info!("Encrypting the password.");
+                let mut salt = vec![0u8; SALT_LENGTH];
+                OsRng.fill_bytes(&mut salt);
+
+                let database_secret = self.get_database_secret()?;
+                let key = self.derive_key(&database_secret, &salt)?;
+                let cipher = Aes256Gcm::new(&key.into());
+                let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+                let password_encrypted = cipher.encrypt(&nonce, password.as_bytes())?;
+
+                info!("Storing the password into the database.");
+                self.get_database()?.execute(
+                    "INSERT OR REPLACE INTO Passwords (url, user, password_encrypted, password_nonce, salt) VALUES (?1, ?2, ?3, ?4, ?5)",
+                    (
+                        url.to_string(),
+                        user.to_string(),
+                        password_encrypted,
+                        nonce.as_slice().to_vec(),
+                        salt
+                ))?;
+
+                Ok(())
+            }
+        }
+    }
+
+    #[tracing::instrument]
+    fn delete_password(&mut self, url: &str, user: &str) -> Result<()> {
+        match self.store {
+            CredentialStore::Keyring => {
+                info!("Removing entry from operating system credential store.");
+                let entry = self.get_entry(url, user)?;
+                match entry.delete_credential() {
+                    // The password is already gone, such as when pruning.
+                    Ok(_) | Err(keyring::Error::NoEntry) => {},
+                    Err(error) => return Err(error.into())
+                }
+                self.entry_cache.remove(&(url.to_string(), user.to_string()));
+            },
+            CredentialStore::Database => {
+                info!("Removing password from the database.");
+                self.get_database()?.execute(
+                    "DELETE FROM Passwords WHERE url=?1 AND user=?2",
+                    (url.to_string(), user.to_string()),
+                )?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Returns whether the password can be found, without reading it.
+    #[tracing::instrument]
+    pub fn has_password(&mut self, url: &str, user: &str) -> Result<bool> {
+        match self.store {
+            CredentialStore::Keyring => {
+                let entry = self.get_entry(url, user)?;
+                match entry.get_password() {
+                    Ok(_) => Ok(true),
+                    Err(keyring::Error::NoEntry) => Ok(false),
+                    Err(error) => Err(error.into())
+                }
+            },
+            CredentialStore::Database => {
+                let database = self.get_database()?;
+                let mut stmt: rusqlite::Statement<'_> = database.prepare(
+                    "SELECT 1 FROM Passwords WHERE url=:url AND user=:user;")?;
+
+                Ok(stmt.exists(&[(":url", url), (":user", user)])?)
+            }
+        }
+    }
+
+    /// Lists every stored credential.
+    #[tracing::instrument]
+    pub fn list_credentials(&self) -> Result<Vec<StoredCredential>> {
+        let database = self.get_database()?;
+
+        info!("Selecting credentials from user database.");
+        let mut stmt: rusqlite::Statement<'_> = database.prepare(
+            "SELECT Credentials.url, Credentials.user, DefaultUsers.user IS NOT NULL, Credentials.device_id_nonce IS NOT NULL,
+                    TotpSecrets.user IS NOT NULL, LastUsed.last_used
+             FROM Credentials
+             LEFT JOIN DefaultUsers ON DefaultUsers.url = Credentials.url AND DefaultUsers.user = Credentials.user
+             LEFT JOIN TotpSecrets ON TotpSecrets.url = Credentials.url AND TotpSecrets.user = Credentials.user
+             LEFT JOIN LastUsed ON LastUsed.url = Credentials.url AND LastUsed.user = Credentials.user
+             ORDER BY Credentials.url, Credentials.user;")?;
+        let rows = stmt.query_map([], |row| {
+            Ok(StoredCredential {
+                url: row.get(0)?,
+                user: row.get(1)?,
+                is_default: row.get(2)?,
+                has_device_id: row.get(3)?,
+                has_totp_secret: row.get(4)?,
+                last_used: row.get::<_, Option<u64>>(5)?.map(|seconds| UNIX_EPOCH + Duration::from_secs(seconds))
+            })
+        })?.filter_map(|r| r.ok()).collect::<Vec<StoredCredential>>();
+
+        Ok(rows)
+    }
+
+    /// Records that the credential was used to transfer objects, as shown by `credentials list`.
+    #[tracing::instrument]
+    pub fn set_last_used(&self, url: &str, user: &str) -> Result<()> {
+        let url_string = self.clean_url(url);
+        let url= url_string.as_str();
+
+        let last_used = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+
+        // Credentials from the environment are not stored, so nothing is recorded for them.
+        self.get_database()?.execute(
+            "INSERT OR REPLACE INTO LastUsed (url, user, last_used)
+             SELECT url, user, ?3 FROM Credentials WHERE url=?1 AND user=?2",
+            (url.to_string(), user.to_string(), last_used),
+        )?;
+
+        Ok(())
+    }
+
+    /// Moves every password into another store, returning how many were moved.
+    #[tracing::instrument]
+    pub fn migrate(&mut self, to: CredentialStore) -> Result<usize> {
+        let from = self.store;
+        if from == to {
+            return Ok(0);
+        }
+
+        let database = self.get_database()?;
+        let mut stmt: rusqlite::Statement<'_> = database.prepare("SELECT url, user FROM Credentials;")?;
+        let rows = stmt
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .filter_map(|r| r.ok())
+            .collect::<Vec<(String, String)>>();
+        drop(stmt);
+
+        for (url, user) in &rows {
+            info!("Moving the password for \"{}\" at \"{}\" from the {} to the {}.", user, url, from, to);
+            let password = self.get_password(url, user)?;
+
+            // Write to the new store before removing from the old one so that a failure never loses the password.
+            self.store = to;
+            let result = self.set_password(url, user, &password);
+            self.store = from;
+            result?;
+
+            self.delete_password(url, user)?;
+        }
+
+        self.store = to;
+
+        Ok(rows.len())
+    }
+
     #[tracing::instrument]
     fn get_entry(&mut self, url: &str, user: &str) -> Result<&Entry>{
         if let std::collections::hash_map::Entry::Vacant(e) = self.entry_cache.entry((url.to_string(), user.to_string())) {
@@ -238,8 +735,48 @@ impl CredentialManager {
         output
     }
 
+    /// The key used before version 2, which only exists for passwords of at most 32 bytes.
+    #[tracing::instrument(skip(password))]
+    fn legacy_key(&self, password: &str) -> Result<Key<Aes256Gcm>> {
+        let padded_password = self.pad_string(password);
+        if padded_password.len() != KEY_LENGTH {
+            bail!("The password is too long to decrypt a value stored without a salt.");
+        }
+
+        Ok(*Key::<Aes256Gcm>::from_slice(padded_password.as_bytes()))
+    }
+
+    /// Returns the ciphertext, nonce and salt.
+    #[tracing::instrument(skip(password, plaintext))]
+    fn encrypt(&self, password: &str, plaintext: &[u8]) -> Result<(Vec<u8>, Vec<u8>, Vec<u8>)> {
+        let mut salt = vec![0u8; SALT_LENGTH];
+        OsRng.fill_bytes(&mut salt);
+
+        let key = self.derive_key(password.as_bytes(), &salt)?;
+
+        let cipher = Aes256Gcm::new(&key.into());
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng); // 96-bits; unique per message
+        let ciphertext = cipher.encrypt(&nonce, plaintext)?;
+
+        Ok((ciphertext, nonce.as_slice().to_vec(), salt))
+    }
+
+    #[tracing::instrument(skip(password, ciphertext))]
+    fn decrypt(&self, password: &str, ciphertext: &[u8], nonce: Vec<u8>, salt: Option<&[u8]>) -> Result<Vec<u8>> {
+        let key = match salt {
+            Some(salt) => self.derive_key(password.as_bytes(), salt)?.into(),
+            None => self.legacy_key(password)?
+        };
+
+        let cipher = Aes256Gcm::new(&key);
+        let nonce = Nonce::from_iter(nonce);
+
+        Ok(cipher.decrypt(&nonce, ciphertext)?)
+    }
+
+    /// Returns the credential for the user, or for the default user of the URL if no user is given.
     #[tracing::instrument]
-    pub fn get_credential(&mut self, url: &str) -> Result<Option<Credential>> {
+    pub fn get_credential(&mut self, url: &str, user: Option<&str>) -> Result<Option<Credential>> {
         let url_string = self.clean_url(url);
         let url= url_string.as_str();
 
@@ -248,37 +785,124 @@ impl CredentialManager {
             return Ok(None);
         }
 
+        let user = match user {
+            Some(user) => Some(user.to_string()),
+            None => self.get_default_user(url)?
+        };
+
         info!("Getting entry from sqlite database.");
         let database_rows = self.get_database_credential_iter(url)?;
-        let database_credential = database_rows.first().context("No elements returned from database.")?;
+        let database_credential = match &user {
+            Some(user) => match database_rows.iter().find(|row| &row.user == user) {
+                Some(database_credential) => database_credential,
+                None => {
+                    debug!(url=url, user=user, "User did not exist in sqlite database.");
+                    return Ok(None);
+                }
+            },
+            None => database_rows.first().context("No elements returned from database.")?
+        };
 
-        info!("Getting password from operating system credential store.");
-        let entry = self.get_entry(url, &database_credential.user)?;
-        let password = entry.get_password()?;
+        let password = self.get_password(url, &database_credential.user)?;
 
         let mut device_id: Option<String> = None;
         if let Some(nonce_vec) = database_credential.device_id_nonce.clone() {
             info!("Database has device id, decrypting.");
 
-            let padded_password = self.pad_string(password.as_str());
-            let key: &Key<Aes256Gcm> = padded_password.as_bytes().into();
+            let plaintext = self.decrypt(
+                password.as_str(),
+                database_credential.device_id_encrypted.clone().context("Device ID is empty.")?.as_ref(),
+                nonce_vec,
+                database_credential.device_id_salt.as_deref())?;
 
-            let cipher = Aes256Gcm::new(key);
-            let nonce = Nonce::from_iter(nonce_vec);
-            let plaintext = cipher.decrypt(
-                &nonce, 
-                database_credential.device_id_encrypted.clone().context("Device ID is empty.")?.as_ref())?;
-                device_id = Some(String::from_utf8(plaintext)?);
+            if database_credential.device_id_salt.is_none() {
+                info!("Re-encrypting the device id with a salt.");
+                let (ciphertext, nonce, salt) = self.encrypt(password.as_str(), &plaintext)?;
+
+                self.get_database()?.execute(
+                    "UPDATE Credentials SET device_id_encrypted=?1, device_id_nonce=?2, device_id_salt=?3 WHERE url=?4 AND user=?5",
+                    (ciphertext, nonce, salt, url.to_string(), database_credential.user.to_string()),
+                )?;
+            }
+
+            device_id = Some(String::from_utf8(plaintext)?);
 
             info!("Decryption completed.")
         }
 
         let mut credential = Credential::new(database_credential.user.clone(), password);
         credential.device_id = device_id;
+        credential.certificate_fingerprint = self.get_certificate_fingerprint(url)?;
+        credential.ca_bundle = self.get_ca_bundle(url)?;
+        credential.totp_secret = self.get_totp_secret(url, &credential)?;
 
         Ok(Some(credential))
     }
 
+    #[tracing::instrument]
+    fn get_totp_secret(&self, url: &str, credential: &Credential) -> Result<Option<String>> {
+        let database = self.get_database()?;
+
+        info!("Selecting TOTP secret from user database.");
+        let mut stmt: rusqlite::Statement<'_> = database.prepare(
+            "SELECT secret_encrypted, secret_nonce, secret_salt FROM TotpSecrets WHERE url=:url AND user=:user;")?;
+        let rows = stmt
+            .query_map(&[(":url", url), (":user", credential.user.as_str())], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))?
+            .filter_map(|r| r.ok())
+            .collect::<Vec<(Vec<u8>, Vec<u8>, Option<Vec<u8>>)>>();
+
+        match rows.into_iter().next() {
+            Some((secret_encrypted, secret_nonce, secret_salt)) => {
+                info!("Database has TOTP secret, decrypting.");
+
+                let plaintext = self.decrypt(credential.password.as_str(), &secret_encrypted, secret_nonce, secret_salt.as_deref())?;
+
+                if secret_salt.is_none() {
+                    info!("Re-encrypting the TOTP secret with a salt.");
+                    let (ciphertext, nonce, salt) = self.encrypt(credential.password.as_str(), &plaintext)?;
+
+                    database.execute(
+                        "UPDATE TotpSecrets SET secret_encrypted=?1, secret_nonce=?2, secret_salt=?3 WHERE url=?4 AND user=?5",
+                        (ciphertext, nonce, salt, url.to_string(), credential.user.to_string()),
+                    )?;
+                }
+
+                Ok(Some(String::from_utf8(plaintext)?))
+            },
+            None => Ok(None)
+        }
+    }
+
+    #[tracing::instrument]
+    fn get_certificate_fingerprint(&self, url: &str) -> Result<Option<String>> {
+        let database = self.get_database()?;
+
+        info!("Selecting certificate fingerprint from user database.");
+        let mut stmt: rusqlite::Statement<'_> = database.prepare(
+            "SELECT fingerprint FROM CertificateFingerprints WHERE url=:url;")?;
+        let rows = stmt
+            .query_map(&[(":url", url)], |row| row.get(0))?
+            .filter_map(|r| r.ok())
+            .collect::<Vec<String>>();
+
+        Ok(rows.first().cloned())
+    }
+
+    #[tracing::instrument]
+    fn get_ca_bundle(&self, url: &str) -> Result<Option<PathBuf>> {
+        let database = self.get_database()?;
+
+        info!("Selecting CA bundle from user database.");
+        let mut stmt: rusqlite::Statement<'_> = database.prepare(
+            "SELECT path FROM CaBundles WHERE url=:url;")?;
+        let rows = stmt
+            .query_map(&[(":url", url)], |row| row.get(0))?
+            .filter_map(|r| r.ok())
+            .collect::<Vec<String>>();
+
+        Ok(rows.first().map(PathBuf::from))
+    }
+
     #[tracing::instrument]
     pub fn has_credential(&self, url: &str) -> Result<bool> {
         let url_string = self.clean_url(url);
@@ -289,57 +913,140 @@ impl CredentialManager {
         Ok(!database_rows.is_empty())
     }
 
+    /// Returns the users with a credential for the URL.
+    #[tracing::instrument]
+    pub fn get_users(&self, url: &str) -> Result<Vec<String>> {
+        let url_string = self.clean_url(url);
+        let url= url_string.as_str();
+
+        Ok(self.get_database_credential_iter(url)?.into_iter().map(|row| row.user).collect())
+    }
+
     #[tracing::instrument]
-    pub fn remove_credential(&mut self, url: &str) -> Result<()> {
+    pub fn get_default_user(&self, url: &str) -> Result<Option<String>> {
         let url_string = self.clean_url(url);
         let url= url_string.as_str();
 
-        if self.has_credential(url)? {
-            debug!(url=url, "Entry found in sqlite database.");
+        let database = self.get_database()?;
 
-            let database_rows = self.get_database_credential_iter(url)?;
-            let database_credential = database_rows.first().context("No elements returned from database.")?;
+        info!("Selecting default user from user database.");
+        let mut stmt: rusqlite::Statement<'_> = database.prepare(
+            "SELECT user FROM DefaultUsers WHERE url=:url;")?;
+        let rows = stmt
+            .query_map(&[(":url", url)], |row| row.get(0))?
+            .filter_map(|r| r.ok())
+            .collect::<Vec<String>>();
 
-            info!("Removing entry from operating system credential store.");
-            let entry = self.get_entry(url, &database_credential.user)?;
-            entry.delete_credential()?;
-            self.entry_cache.remove(&(url.to_string(), database_credential.user.to_string()));
+        Ok(rows.first().cloned())
+    }
 
-            info!("Removing entry from sqlite database.");
-            let database = self.get_database()?;
+    #[tracing::instrument]
+    pub fn set_default_user(&self, url: &str, user: &str) -> Result<()> {
+        let url_string = self.clean_url(url);
+        let url= url_string.as_str();
 
-            database.execute(
-                "DELETE FROM Credentials WHERE url=?1",
-                [url].map(|n| n.to_string()),
-            )?;
+        info!("Storing default user into database.");
+        self.get_database()?.execute(
+            "INSERT OR REPLACE INTO DefaultUsers (url, user) VALUES (?1, ?2)",
+            (url.to_string(), user.to_string()),
+        )?;
+
+        Ok(())
+    }
+
+    /// Removes the credential for the user, or for every user of the URL if no user is given.
+    #[tracing::instrument]
+    pub fn remove_credential(&mut self, url: &str, user: Option<&str>) -> Result<()> {
+        let url_string = self.clean_url(url);
+        let url= url_string.as_str();
+
+        let users = self.get_users(url)?
+            .into_iter()
+            .filter(|stored_user| user.is_none_or(|user| user == stored_user))
+            .collect::<Vec<String>>();
+
+        for user in &users {
+            debug!(url=url, user=user, "Entry found in sqlite database.");
+
+            self.delete_password(url, user)?;
+            self.remove_user_rows(url, user)?;
+        }
+
+        let remaining_users = self.get_users(url)?;
+        let database = self.get_database()?;
+        match remaining_users.first() {
+            None => {
+                info!("No users remain, removing the URL from sqlite database.");
+                database.execute(
+                    "DELETE FROM CertificateFingerprints WHERE url=?1",
+                    [url].map(|n| n.to_string()),
+                )?;
+                database.execute(
+                    "DELETE FROM CaBundles WHERE url=?1",
+                    [url].map(|n| n.to_string()),
+                )?;
+                database.execute(
+                    "DELETE FROM DefaultUsers WHERE url=?1",
+                    [url].map(|n| n.to_string()),
+                )?;
+            },
+            Some(remaining_user) => {
+                let default_user = self.get_default_user(url)?;
+                if default_user.is_none_or(|default_user| users.contains(&default_user)) {
+                    info!("The default user was removed, choosing another.");
+                    self.set_default_user(url, remaining_user)?;
+                }
+            }
         }
 
         Ok(())
     }
 
+    #[tracing::instrument]
+    fn remove_user_rows(&self, url: &str, user: &str) -> Result<()> {
+        info!("Removing entry from sqlite database.");
+        let database = self.get_database()?;
+
+        database.execute(
+            "DELETE FROM Credentials WHERE url=?1 AND user=?2",
+            [url, user].map(|n| n.to_string()),
+        )?;
+        database.execute(
+            "DELETE FROM Sessions WHERE url=?1 AND user=?2",
+            [url, user].map(|n| n.to_string()),
+        )?;
+        database.execute(
+            "DELETE FROM TotpSecrets WHERE url=?1 AND user=?2",
+            [url, user].map(|n| n.to_string()),
+        )?;
+        database.execute(
+            "DELETE FROM LastUsed WHERE url=?1 AND user=?2",
+            [url, user].map(|n| n.to_string()),
+        )?;
+
+        Ok(())
+    }
+
     #[tracing::instrument]
     pub fn set_credential(&mut self, url: &str, credential: &Credential) -> Result<()> {
         let url_string = self.clean_url(url);
         let url= url_string.as_str();
 
-        if self.has_credential(url)? {
+        if self.get_users(url)?.contains(&credential.user) {
             debug!("Credential exists already.  Removing it before continuing.");
-            self.remove_credential(url)?;
+            self.remove_user_rows(url, &credential.user)?;
         }
 
         let mut device_id_encrypted: Option<Vec<u8>> = None;
         let mut device_id_nonce: Option<Vec<u8>> = None;
+        let mut device_id_salt: Option<Vec<u8>> = None;
         if let Some(device_id) = credential.device_id.clone() {
             info!("Encrypting the device id.");
-            let padded_password = self.pad_string(credential.password.as_str());
-            let key: &Key<Aes256Gcm> = padded_password.as_bytes().into();
-
-            let cipher = Aes256Gcm::new(key);
-            let nonce = Aes256Gcm::generate_nonce(&mut OsRng); // 96-bits; unique per message
-            let ciphertext = cipher.encrypt(&nonce, device_id.as_bytes())?;
+            let (ciphertext, nonce, salt) = self.encrypt(credential.password.as_str(), device_id.as_bytes())?;
 
             device_id_encrypted = Some(ciphertext);
-            device_id_nonce = Some(nonce.as_slice().to_vec());
+            device_id_nonce = Some(nonce);
+            device_id_salt = Some(salt);
 
             info!("Finished encrypting the device id.");
         }
@@ -347,17 +1054,131 @@ impl CredentialManager {
         info!("Storing credential into database.");
         let database = self.get_database()?;
         database.execute(
-            "INSERT INTO Credentials (url, user, device_id_encrypted, device_id_nonce) VALUES (?1, ?2, ?3, ?4)",
+            "INSERT INTO Credentials (url, user, device_id_encrypted, device_id_nonce, device_id_salt) VALUES (?1, ?2, ?3, ?4, ?5)",
             (
                 url.to_string(),
                 credential.user.to_string(),
                 device_id_encrypted,
                 device_id_nonce,
+                device_id_salt,
         ))?;
 
-        info!("Storing the database into the operating system credential store.");
-        let entry = self.get_entry(url, &credential.user)?;
-        entry.set_password(&credential.password)?;
+        if let Some(totp_secret) = &credential.totp_secret {
+            info!("Encrypting the TOTP secret.");
+            let (secret_encrypted, secret_nonce, secret_salt) = self.encrypt(credential.password.as_str(), totp_secret.as_bytes())?;
+
+            database.execute(
+                "INSERT OR REPLACE INTO TotpSecrets (url, user, secret_encrypted, secret_nonce, secret_salt) VALUES (?1, ?2, ?3, ?4, ?5)",
+                (
+                    url.to_string(),
+                    credential.user.to_string(),
+                    secret_encrypted,
+                    secret_nonce,
+                    secret_salt
+            ))?;
+        }
+
+        if let Some(certificate_fingerprint) = &credential.certificate_fingerprint {
+            info!("Storing the certificate fingerprint into database.");
+            database.execute(
+                "INSERT OR REPLACE INTO CertificateFingerprints (url, fingerprint) VALUES (?1, ?2)",
+                (
+                    url.to_string(),
+                    certificate_fingerprint.to_string()
+            ))?;
+        }
+
+        if let Some(ca_bundle) = &credential.ca_bundle {
+            info!("Storing the CA bundle into database.");
+            database.execute(
+                "INSERT OR REPLACE INTO CaBundles (url, path) VALUES (?1, ?2)",
+                (
+                    url.to_string(),
+                    ca_bundle.to_string_lossy().to_string()
+            ))?;
+        }
+
+        // The first account stored for a URL becomes its default.
+        database.execute(
+            "INSERT OR IGNORE INTO DefaultUsers (url, user) VALUES (?1, ?2)",
+            (url.to_string(), credential.user.to_string()),
+        )?;
+
+        self.set_password(url, &credential.user, &credential.password)?;
+
+        Ok(())
+    }
+
+    /// Returns the cached session for the credential if it has not expired.
+    #[tracing::instrument]
+    pub fn get_session(&self, url: &str, credential: &Credential) -> Result<Option<String>> {
+        let url_string = self.clean_url(url);
+        let url= url_string.as_str();
+
+        let database = self.get_database()?;
+
+        info!("Selecting session from user database.");
+        let mut stmt: rusqlite::Statement<'_> = database.prepare(
+            "SELECT sid_encrypted, sid_nonce, sid_salt, expires FROM Sessions WHERE url=:url AND user=:user;")?;
+        let rows = stmt
+            .query_map(&[(":url", url), (":user", credential.user.as_str())], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)))?
+            .filter_map(|r| r.ok())
+            .collect::<Vec<(Vec<u8>, Vec<u8>, Vec<u8>, u64)>>();
+
+        match rows.into_iter().next() {
+            Some((sid_encrypted, sid_nonce, sid_salt, expires)) => {
+                if expires <= SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs() {
+                    info!("Cached session has expired.");
+
+                    return Ok(None);
+                }
+
+                // A session encrypted with an old password can't be decrypted and is simply not reused.
+                match self.decrypt(credential.password.as_str(), &sid_encrypted, sid_nonce, Some(&sid_salt)) {
+                    Ok(sid) => Ok(Some(String::from_utf8(sid)?)),
+                    Err(_) => Ok(None)
+                }
+            },
+            None => Ok(None)
+        }
+    }
+
+    #[tracing::instrument]
+    pub fn remove_session(&self, url: &str, user: &str) -> Result<()> {
+        let url_string = self.clean_url(url);
+        let url= url_string.as_str();
+
+        info!("Removing session from sqlite database.");
+        let database = self.get_database()?;
+        database.execute(
+            "DELETE FROM Sessions WHERE url=?1 AND user=?2",
+            (url.to_string(), user.to_string()),
+        )?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(skip(sid))]
+    pub fn set_session(&self, url: &str, credential: &Credential, sid: &str, lifetime: Duration) -> Result<()> {
+        let url_string = self.clean_url(url);
+        let url= url_string.as_str();
+
+        info!("Encrypting the session.");
+        let (sid_encrypted, sid_nonce, sid_salt) = self.encrypt(credential.password.as_str(), sid.as_bytes())?;
+        let expires = (SystemTime::now() + lifetime).duration_since(UNIX_EPOCH)?.as_secs();
+
+        info!("Storing session into database.");
+        let database = self.get_database()?;
+        database.execute(
+            "INSERT OR REPLACE INTO Sessions (url, user, sid_encrypted, sid_nonce, sid_salt, expires) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            (
+                url.to_string(),
+                credential.user.to_string(),
+                sid_encrypted,
+                sid_nonce,
+                sid_salt,
+                expires
+        ))?;
 
         Ok(())
     }
@@ -365,13 +1186,15 @@ impl CredentialManager {
 
 #[cfg(test)]
 mod tests {
-    use std::collections::HashMap;
+    use std::{collections::HashMap, path::PathBuf, time::Duration};
+
+    use aes_gcm::{aead::{Aead, OsRng}, AeadCore, Aes256Gcm, KeyInit};
 
     use anyhow::Context;
     use keyring::{mock, set_default_credential_builder};
     use rusqlite::Connection;
 
-    use super::{Credential, CredentialError, CredentialManager};
+    use super::{Credential, CredentialError, CredentialManager, CredentialStore};
 
     fn create_version_0_database(connection: &Connection) -> anyhow::Result<()> {
         connection.execute(
@@ -439,7 +1262,9 @@ mod tests {
     fn empty_database_uninitialized() {
         let credential_manager = CredentialManager {
             connection: Connection::open_in_memory().unwrap(),
-            entry_cache: HashMap::new()
+            entry_cache: HashMap::new(),
+            store: CredentialStore::Keyring,
+            database_secret: None
         };
 
         let result = credential_manager.get_database_version(&credential_manager.connection);
@@ -448,7 +1273,7 @@ mod tests {
             Ok(_) => panic!(),
             Err(err) => match err {
                 CredentialError::DatabaseNotInitialized => (),
-                _ => Err(err).unwrap()
+                _ => panic!("{:?}", err)
             }
         }
     }
@@ -460,7 +1285,9 @@ mod tests {
 
         let credential_manager = CredentialManager {
             connection,
-            entry_cache: HashMap::new()
+            entry_cache: HashMap::new(),
+            store: CredentialStore::Keyring,
+            database_secret: None
         };
 
         let version = credential_manager.get_database_version(&credential_manager.connection).unwrap();
@@ -475,7 +1302,9 @@ mod tests {
 
         let credential_manager = CredentialManager {
             connection,
-            entry_cache: HashMap::new()
+            entry_cache: HashMap::new(),
+            store: CredentialStore::Keyring,
+            database_secret: None
         };
 
         let version = credential_manager.get_database_version(&credential_manager.connection).unwrap();
@@ -484,19 +1313,199 @@ mod tests {
     }
 
     #[test]
-    fn database_upgraded_from_0_to_1() {
+    fn database_upgraded_from_0_to_2() {
         let connection = Connection::open_in_memory().unwrap();
         create_version_0_database(&connection).unwrap();
 
         let credential_manager = CredentialManager {
             connection,
-            entry_cache: HashMap::new()
+            entry_cache: HashMap::new(),
+            store: CredentialStore::Keyring,
+            database_secret: None
         };
 
         let connection = credential_manager.get_database().unwrap();
         let version = credential_manager.get_database_version(connection).unwrap();
         
-        assert_eq!(version, 1);
+        assert_eq!(version, 2);
+    }
+
+    #[test]
+    fn database_upgraded_from_1_to_2_reencrypts_device_id() {
+        set_default_credential_builder(mock::default_credential_builder()); // Set mock
+
+        let connection = Connection::open_in_memory().unwrap();
+        create_version_1_database(&connection).unwrap();
+
+        let mut credential_manager = CredentialManager {
+            connection,
+            entry_cache: HashMap::new(),
+            store: CredentialStore::Keyring,
+            database_secret: None
+        };
+
+        // Store a device id the way version 1 did, with the padded password as the key.
+        let cipher = Aes256Gcm::new(&credential_manager.legacy_key("test_password").unwrap());
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+        let device_id_encrypted = cipher.encrypt(&nonce, "12345".as_bytes()).unwrap();
+        credential_manager.connection.execute(
+            "INSERT INTO Credentials (url, user, device_id_encrypted, device_id_nonce) VALUES (?1, ?2, ?3, ?4)",
+            ("http://example.com", "test_user", device_id_encrypted, nonce.as_slice().to_vec())
+        ).unwrap();
+        credential_manager.set_password("http://example.com", "test_user", "test_password").unwrap();
+
+        let credential: Credential = credential_manager.get_credential("http://example.com", None).unwrap().context("Credential expected").unwrap();
+
+        assert_eq!(credential.device_id, Some("12345".to_string()));
+        assert_eq!(credential_manager.get_database_version(&credential_manager.connection).unwrap(), 2);
+
+        let device_id_salt: Option<Vec<u8>> = credential_manager.connection.query_row(
+            "SELECT device_id_salt FROM Credentials WHERE url='http://example.com'", [], |row| row.get(0)).unwrap();
+        assert!(device_id_salt.is_some());
+
+        let credential: Credential = credential_manager.get_credential("http://example.com", None).unwrap().context("Credential expected").unwrap();
+
+        assert_eq!(credential.device_id, Some("12345".to_string()));
+    }
+
+    #[test]
+    fn set_get_credential_long_password() {
+        set_default_credential_builder(mock::default_credential_builder()); // Set mock
+
+        let mut credential_manager = CredentialManager {
+            connection: Connection::open_in_memory().unwrap(),
+            entry_cache: HashMap::new(),
+            store: CredentialStore::Keyring,
+            database_secret: None
+        };
+
+        let password = "a password that is much longer than thirty two bytes";
+        credential_manager.set_credential("http://example.com", &new_credential("test_user", password, Some("12345"))).unwrap();
+
+        let credential: Credential = credential_manager.get_credential("http://example.com", None).unwrap().context("Credential expected").unwrap();
+
+        assert_eq!(credential.password, password.to_string());
+        assert_eq!(credential.device_id, Some("12345".to_string()));
+    }
+
+    #[test]
+    fn migrate_to_version_2_already_upgraded() {
+        let connection = Connection::open_in_memory().unwrap();
+        create_version_1_database(&connection).unwrap();
+
+        let credential_manager = CredentialManager {
+            connection,
+            entry_cache: HashMap::new(),
+            store: CredentialStore::Keyring,
+            database_secret: None
+        };
+
+        // Another agent may upgrade the database between reading the version and starting the upgrade.
+        credential_manager.migrate_to_version_2(&credential_manager.connection).unwrap();
+        credential_manager.migrate_to_version_2(&credential_manager.connection).unwrap();
+
+        assert_eq!(credential_manager.get_database_version(&credential_manager.connection).unwrap(), 2);
+        assert!(credential_manager.connection.is_autocommit());
+    }
+
+    #[test]
+    fn set_get_credential_certificate_fingerprint() {
+        set_default_credential_builder(mock::default_credential_builder()); // Set mock
+
+        let mut credential_manager = CredentialManager {
+            connection: Connection::open_in_memory().unwrap(),
+            entry_cache: HashMap::new(),
+            store: CredentialStore::Keyring,
+            database_secret: None
+        };
+
+        let mut credential = new_credential("test_user", "test_password", None);
+        credential.certificate_fingerprint = Some("ab".repeat(32));
+        credential_manager.set_credential("http://example.com", &credential).unwrap();
+
+        let credential: Credential = credential_manager.get_credential("http://example.com", None).unwrap().context("Credential expected").unwrap();
+
+        assert_eq!(credential.certificate_fingerprint, Some("ab".repeat(32)));
+    }
+
+    #[test]
+    fn set_get_credential_ca_bundle() {
+        set_default_credential_builder(mock::default_credential_builder()); // Set mock
+
+        let mut credential_manager = CredentialManager {
+            connection: Connection::open_in_memory().unwrap(),
+            entry_cache: HashMap::new(),
+            store: CredentialStore::Keyring,
+            database_secret: None
+        };
+
+        let mut credential = new_credential("test_user", "test_password", None);
+        credential.ca_bundle = Some(PathBuf::from("/path/to/ca.pem"));
+        credential_manager.set_credential("http://example.com", &credential).unwrap();
+
+        let credential: Credential = credential_manager.get_credential("http://example.com", None).unwrap().context("Credential expected").unwrap();
+
+        assert_eq!(credential.ca_bundle, Some(PathBuf::from("/path/to/ca.pem")));
+    }
+
+    #[test]
+    fn set_get_credential_totp_secret() {
+        set_default_credential_builder(mock::default_credential_builder()); // Set mock
+
+        let mut credential_manager = CredentialManager {
+            connection: Connection::open_in_memory().unwrap(),
+            entry_cache: HashMap::new(),
+            store: CredentialStore::Keyring,
+            database_secret: None
+        };
+
+        let mut credential = new_credential("test_user", "test_password", None);
+        credential.totp_secret = Some("GEZDGNBVGY3TQOJQ".to_string());
+        credential_manager.set_credential("http://example.com", &credential).unwrap();
+
+        let credential: Credential = credential_manager.get_credential("http://example.com", None).unwrap().context("Credential expected").unwrap();
+
+        assert_eq!(credential.totp_secret, Some("GEZDGNBVGY3TQOJQ".to_string()));
+    }
+
+    #[test]
+    fn set_get_credential_database_store() {
+        let mut credential_manager = CredentialManager {
+            connection: Connection::open_in_memory().unwrap(),
+            entry_cache: HashMap::new(),
+            store: CredentialStore::Database,
+            database_secret: Some(b"test_passphrase".to_vec())
+        };
+
+        credential_manager.set_credential("http://example.com", &new_credential("test_user", "test_password", Some("test_device_id"))).unwrap();
+
+        let credential: Credential = credential_manager.get_credential("http://example.com", None).unwrap().context("Credential expected").unwrap();
+
+        assert_eq!(credential.password, "test_password".to_string());
+        assert_eq!(credential.device_id, Some("test_device_id".to_string()));
+
+        credential_manager.database_secret = Some(b"wrong_passphrase".to_vec());
+
+        assert!(credential_manager.get_credential("http://example.com", None).is_err());
+    }
+
+    #[test]
+    fn set_get_session() {
+        let credential_manager = CredentialManager {
+            connection: Connection::open_in_memory().unwrap(),
+            entry_cache: HashMap::new(),
+            store: CredentialStore::Keyring,
+            database_secret: None
+        };
+
+        let credential = new_credential("test_user", "test_password", None);
+        credential_manager.set_session("http://example.com", &credential, "test_sid", Duration::from_secs(60)).unwrap();
+
+        assert_eq!(credential_manager.get_session("http://example.com", &credential).unwrap(), Some("test_sid".to_string()));
+
+        credential_manager.set_session("http://example.com", &credential, "test_sid", Duration::ZERO).unwrap();
+
+        assert_eq!(credential_manager.get_session("http://example.com", &credential).unwrap(), None);
     }
 
     #[test]
@@ -505,12 +1514,14 @@ mod tests {
 
         let mut credential_manager = CredentialManager {
             connection: Connection::open_in_memory().unwrap(),
-            entry_cache: HashMap::new()
+            entry_cache: HashMap::new(),
+            store: CredentialStore::Keyring,
+            database_secret: None
         };
 
-        credential_manager.set_credential("http://example.com", &&new_credential("test_user", "test_password", None)).unwrap();
+        credential_manager.set_credential("http://example.com", &new_credential("test_user", "test_password", None)).unwrap();
 
-        let credential: Credential = credential_manager.get_credential("http://example.com").unwrap().context("Credential expected").unwrap();
+        let credential: Credential = credential_manager.get_credential("http://example.com", None).unwrap().context("Credential expected").unwrap();
 
         assert_eq!(credential.user, "test_user".to_string());
         assert_eq!(credential.password, "test_password".to_string());
@@ -523,12 +1534,14 @@ mod tests {
 
         let mut credential_manager = CredentialManager {
             connection: Connection::open_in_memory().unwrap(),
-            entry_cache: HashMap::new()
+            entry_cache: HashMap::new(),
+            store: CredentialStore::Keyring,
+            database_secret: None
         };
 
-        credential_manager.set_credential("http://example.com/", &&new_credential("test_user", "test_password", None)).unwrap();
+        credential_manager.set_credential("http://example.com/", &new_credential("test_user", "test_password", None)).unwrap();
 
-        let credential: Credential = credential_manager.get_credential("http://example.com").unwrap().context("Credential expected").unwrap();
+        let credential: Credential = credential_manager.get_credential("http://example.com", None).unwrap().context("Credential expected").unwrap();
 
         assert_eq!(credential.user, "test_user".to_string());
         assert_eq!(credential.password, "test_password".to_string());
@@ -541,12 +1554,14 @@ mod tests {
 
         let mut credential_manager = CredentialManager {
             connection: Connection::open_in_memory().unwrap(),
-            entry_cache: HashMap::new()
+            entry_cache: HashMap::new(),
+            store: CredentialStore::Keyring,
+            database_secret: None
         };
 
-        credential_manager.set_credential("http://example.com", &&new_credential("test_user", "test_password", Some("12345"))).unwrap();
+        credential_manager.set_credential("http://example.com", &new_credential("test_user", "test_password", Some("12345"))).unwrap();
 
-        let credential: Credential = credential_manager.get_credential("http://example.com").unwrap().context("Credential expected").unwrap();
+        let credential: Credential = credential_manager.get_credential("http://example.com", None).unwrap().context("Credential expected").unwrap();
 
         assert_eq!(credential.user, "test_user".to_string());
         assert_eq!(credential.password, "test_password".to_string());
@@ -559,10 +1574,12 @@ mod tests {
 
         let mut credential_manager = CredentialManager {
             connection: Connection::open_in_memory().unwrap(),
-            entry_cache: HashMap::new()
+            entry_cache: HashMap::new(),
+            store: CredentialStore::Keyring,
+            database_secret: None
         };
 
-        credential_manager.set_credential("http://example.com", &&new_credential("test_user", "test_password", Some("echo 12345"))).unwrap();
+        credential_manager.set_credential("http://example.com", &new_credential("test_user", "test_password", Some("echo 12345"))).unwrap();
 
         assert!(credential_manager.has_credential("http://example.com").unwrap());
     }
@@ -573,14 +1590,16 @@ mod tests {
 
         let mut credential_manager = CredentialManager {
             connection: Connection::open_in_memory().unwrap(),
-            entry_cache: HashMap::new()
+            entry_cache: HashMap::new(),
+            store: CredentialStore::Keyring,
+            database_secret: None
         };
 
         credential_manager.set_credential("http://example.com", &new_credential("test_user", "test_password", Some("echo 12345"))).unwrap();
 
         credential_manager.set_credential("http://example.com", &new_credential("test_user", "test_password2", None)).unwrap();
 
-        let credential: Credential = credential_manager.get_credential("http://example.com").unwrap().context("Credential expected").unwrap();
+        let credential: Credential = credential_manager.get_credential("http://example.com", None).unwrap().context("Credential expected").unwrap();
 
         assert_eq!(credential.user, "test_user".to_string());
         assert_eq!(credential.password, "test_password2".to_string());
@@ -593,14 +1612,16 @@ mod tests {
 
         let mut credential_manager = CredentialManager {
             connection: Connection::open_in_memory().unwrap(),
-            entry_cache: HashMap::new()
+            entry_cache: HashMap::new(),
+            store: CredentialStore::Keyring,
+            database_secret: None
         };
 
         credential_manager.set_credential("http://example.com", &new_credential("test_user", "test_password", Some("12345"))).unwrap();
 
         credential_manager.set_credential("http://example.com", &new_credential("test_user", "test_password2", Some("56789"))).unwrap();
 
-        let credential: Credential = credential_manager.get_credential("http://example.com").unwrap().context("Credential expected").unwrap();
+        let credential: Credential = credential_manager.get_credential("http://example.com", None).unwrap().context("Credential expected").unwrap();
 
         assert_eq!(credential.user, "test_user".to_string());
         assert_eq!(credential.password, "test_password2".to_string());
@@ -613,12 +1634,93 @@ mod tests {
 
         let mut credential_manager = CredentialManager {
             connection: Connection::open_in_memory().unwrap(),
-            entry_cache: HashMap::new()
+            entry_cache: HashMap::new(),
+            store: CredentialStore::Keyring,
+            database_secret: None
         };
 
         credential_manager.set_credential("http://example.com", &new_credential("test_user", "test_password", Some("echo 12345"))).unwrap();
-        credential_manager.remove_credential("http://example.com").unwrap();
+        credential_manager.remove_credential("http://example.com", None).unwrap();
 
         assert!(!credential_manager.has_credential("http://example.com").unwrap());
     }
+
+    #[test]
+    fn set_get_credential_multiple_users() {
+        set_default_credential_builder(mock::default_credential_builder()); // Set mock
+
+        let mut credential_manager = CredentialManager {
+            connection: Connection::open_in_memory().unwrap(),
+            entry_cache: HashMap::new(),
+            store: CredentialStore::Keyring,
+            database_secret: None
+        };
+
+        credential_manager.set_credential("http://example.com", &new_credential("test_user", "test_password", None)).unwrap();
+        credential_manager.set_credential("http://example.com", &new_credential("test_user2", "test_password2", None)).unwrap();
+
+        let credential: Credential = credential_manager.get_credential("http://example.com", None).unwrap().context("Credential expected").unwrap();
+        assert_eq!(credential.user, "test_user".to_string());
+
+        let credential: Credential = credential_manager.get_credential("http://example.com", Some("test_user2")).unwrap().context("Credential expected").unwrap();
+        assert_eq!(credential.password, "test_password2".to_string());
+
+        assert!(credential_manager.get_credential("http://example.com", Some("test_user3")).unwrap().is_none());
+
+        credential_manager.set_default_user("http://example.com", "test_user2").unwrap();
+        let credential: Credential = credential_manager.get_credential("http://example.com", None).unwrap().context("Credential expected").unwrap();
+        assert_eq!(credential.user, "test_user2".to_string());
+    }
+
+    #[test]
+    fn list_credentials() {
+        set_default_credential_builder(mock::default_credential_builder()); // Set mock
+
+        let mut credential_manager = CredentialManager {
+            connection: Connection::open_in_memory().unwrap(),
+            entry_cache: HashMap::new(),
+            store: CredentialStore::Keyring,
+            database_secret: None
+        };
+
+        let mut credential = new_credential("test_user", "test_password", Some("12345"));
+        credential.totp_secret = Some("GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ".to_string());
+        credential_manager.set_credential("http://example.com", &credential).unwrap();
+        credential_manager.set_credential("http://example.com", &new_credential("test_user2", "test_password2", None)).unwrap();
+        credential_manager.get_credential("http://example.com", Some("test_user2")).unwrap();
+        credential_manager.set_last_used("http://example.com", "test_user").unwrap();
+
+        let credentials = credential_manager.list_credentials().unwrap();
+
+        assert_eq!(credentials.len(), 2);
+        assert_eq!(credentials[0].user, "test_user".to_string());
+        assert!(credentials[0].is_default);
+        assert!(credentials[0].has_device_id);
+        assert!(credentials[0].has_totp_secret);
+        assert!(credentials[0].last_used.is_some());
+        assert_eq!(credentials[1].user, "test_user2".to_string());
+        assert!(!credentials[1].is_default);
+        assert!(!credentials[1].has_device_id);
+        assert!(!credentials[1].has_totp_secret);
+        assert!(credentials[1].last_used.is_none());
+    }
+
+    #[test]
+    fn remove_credential_single_user() {
+        set_default_credential_builder(mock::default_credential_builder()); // Set mock
+
+        let mut credential_manager = CredentialManager {
+            connection: Connection::open_in_memory().unwrap(),
+            entry_cache: HashMap::new(),
+            store: CredentialStore::Keyring,
+            database_secret: None
+        };
+
+        credential_manager.set_credential("http://example.com", &new_credential("test_user", "test_password", None)).unwrap();
+        credential_manager.set_credential("http://example.com", &new_credential("test_user2", "test_password2", None)).unwrap();
+        credential_manager.remove_credential("http://example.com", Some("test_user")).unwrap();
+
+        assert_eq!(credential_manager.get_users("http://example.com").unwrap(), vec!["test_user2".to_string()]);
+        assert_eq!(credential_manager.get_default_user("http://example.com").unwrap(), Some("test_user2".to_string()));
+    }
 }
\ No newline at end of file
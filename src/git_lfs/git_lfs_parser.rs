@@ -56,6 +56,7 @@ pub fn error_init(code: u32, message: &str) -> Result<()> {
 pub fn complete_upload(oid: &str) -> Result<()> {
     let complete_json = EventJson {
         event: "complete".to_string(),
+        operation: None,
         oid: Some(oid.to_string()),
         path: None,
         size: None
@@ -71,6 +72,7 @@ pub fn complete_upload(oid: &str) -> Result<()> {
 pub fn complete_download(oid: &str, path: &str) -> Result<()> {
     let complete_json = EventJson {
         event: "complete".to_string(),
+        operation: None,
         oid: Some(oid.to_string()),
         path: Some(path.to_string()),
         size: None
@@ -86,6 +88,8 @@ pub fn complete_download(oid: &str, path: &str) -> Result<()> {
 #[derive(Debug, Serialize, Deserialize, Clone)]
 struct EventJson {
     event: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    operation: Option<String>,
     oid: Option<String>,
     path: Option<String>,
     size: Option<usize>
@@ -94,6 +98,7 @@ struct EventJson {
 #[derive(Debug)]
 pub struct Event {
     pub event: EventType,
+    pub operation: Option<String>,
     pub oid: Option<String>,
     pub path: Option<String>,
     pub size: Option<usize>
@@ -144,6 +149,7 @@ impl<'custom_transfer_agent, T: CustomTransferAgent> GitLfsParser<'custom_transf
 
         Ok(Event {
             event: event_type,
+            operation: event.operation.clone(),
             oid: event.oid.clone(),
             path: event.path.clone(),
             size: event.size
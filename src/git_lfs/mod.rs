@@ -1,5 +1,7 @@
 mod custom_transfer_agent;
 mod git_lfs_parser;
+mod pointer;
 
 pub use custom_transfer_agent::CustomTransferAgent;
-pub use git_lfs_parser::{Event, GitLfsParser, error_init, GitLfsProgressReporter};
\ No newline at end of file
+pub use git_lfs_parser::{Event, GitLfsParser, error_init, GitLfsProgressReporter};
+pub use pointer::read_oid;
\ No newline at end of file
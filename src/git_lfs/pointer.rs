@@ -0,0 +1,66 @@
+use std::{fs::File, io::{self, Read}, path::Path};
+
+use anyhow::{Context, Result};
+use sha2::{Digest, Sha256};
+use tracing::info;
+
+// Pointer files are always smaller than this. See https://github.com/git-lfs/git-lfs/blob/main/docs/spec.md.
+const MAX_POINTER_SIZE: u64 = 1024;
+const POINTER_VERSION_PREFIX: &str = "version https://git-lfs.github.com/spec/";
+
+#[tracing::instrument]
+pub fn read_oid(path: &Path) -> Result<String> {
+    let mut file = File::open(path)?;
+
+    if file.metadata()?.len() < MAX_POINTER_SIZE {
+        let mut contents = String::new();
+
+        if file.read_to_string(&mut contents).is_ok() {
+            if let Some(oid) = parse_pointer(&contents) {
+                info!("Found OID in pointer file.");
+
+                return Ok(oid);
+            }
+        }
+
+        file = File::open(path)?;
+    }
+
+    // The file has already been smudged, so the OID is the hash of its contents.
+    info!("File is not a pointer, hashing its contents.");
+    let mut hasher = Sha256::new();
+    io::copy(&mut file, &mut hasher).context("Could not hash the file.")?;
+
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+#[tracing::instrument]
+fn parse_pointer(contents: &str) -> Option<String> {
+    if !contents.starts_with(POINTER_VERSION_PREFIX) {
+        return None;
+    }
+
+    contents
+        .lines()
+        .find_map(|line| line.strip_prefix("oid sha256:"))
+        .map(|oid| oid.trim().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_pointer;
+
+    #[test]
+    fn parse_pointer_oid() {
+        let contents = "version https://git-lfs.github.com/spec/v1\noid sha256:4d7a214614ab2935c943f9e0ff69d22eadbb8f32b1258daaa5e2ca24d17e2393\nsize 12345\n";
+
+        assert_eq!(
+            parse_pointer(contents),
+            Some("4d7a214614ab2935c943f9e0ff69d22eadbb8f32b1258daaa5e2ca24d17e2393".to_string()));
+    }
+
+    #[test]
+    fn parse_pointer_not_pointer() {
+        assert_eq!(parse_pointer("oid sha256:1234\n"), None);
+    }
+}
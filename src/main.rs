@@ -1,5 +1,5 @@
 use anyhow::Result;
-use clap::{crate_version, Arg, Command};
+use clap::{crate_version, value_parser, Arg, ArgAction, Command};
 use tracing::{error, info};
 use users_dirs::get_config_dir;
 use tracing_appender::rolling;
@@ -12,7 +12,7 @@ mod subcommands;
 mod synology_api;
 mod users_dirs;
 
-use subcommands::{LoginSubcommand, LogoutSubcommand, MainSubcommand, Subcommand};
+use subcommands::{CredentialsSubcommand, LoginSubcommand, LogoutSubcommand, MainSubcommand, MoveSubcommand, SearchSubcommand, ShareLinkSubcommand, Subcommand, UsageSubcommand};
 
 fn setup_logging() -> Result<()> {
     let config_path = get_config_dir()?;
@@ -36,6 +36,45 @@ fn cli() -> Command {
         .about("This is an implementation of a git lfs custom transfer agent. See https://github.com/git-lfs/git-lfs/blob/main/docs/custom-transfers.md for more information.")
         .allow_external_subcommands(true)
         .version(crate_version!())
+        .subcommand(
+            Command::new("credentials")
+            .about("Manages the stored credentials.")
+            .subcommand_required(true)
+            .subcommand(
+                Command::new("list")
+                .about("Lists the stored credentials without showing any secrets.")
+            )
+            .subcommand(
+                Command::new("migrate")
+                .about("Moves the stored passwords into another credential store.")
+                .arg(
+                    Arg::new("TO")
+                        .long("to")
+                        .required(true)
+                        .value_parser(["keyring", "database"])
+                        .help("The credential store to move the passwords into")
+                )
+            )
+            .subcommand(
+                Command::new("prune")
+                .about("Removes credentials whose password is missing from the credential store.")
+                .arg(
+                    Arg::new("DRY_RUN")
+                        .long("dry-run")
+                        .action(ArgAction::SetTrue)
+                        .help("Only show the credentials that would be removed")
+                )
+            )
+            .subcommand(
+                Command::new("verify")
+                .about("Logs in to each Synology NAS with its stored credentials.")
+                .arg(
+                    Arg::new("BASE_PATH")
+                        .long("base-path")
+                        .help("The path prefix DSM is served under, for example behind a reverse proxy")
+                )
+            )
+        )
         .subcommand(
             Command::new("login")
                 .about("Allows logging into the Synology NAS.")
@@ -55,6 +94,44 @@ fn cli() -> Command {
                         .required(true)
                         .help("The URL for the Synology NAS")
                 )
+                .arg(
+                    Arg::new("BASE_PATH")
+                        .long("base-path")
+                        .help("The path prefix DSM is served under, for example behind a reverse proxy")
+                )
+                .arg(
+                    Arg::new("PASSWORD_STDIN")
+                        .long("password-stdin")
+                        .action(ArgAction::SetTrue)
+                        .help("Read the password from stdin instead of prompting for it")
+                )
+                .arg(
+                    Arg::new("TOTP")
+                        .long("totp")
+                        .help("The TOTP to use if the Synology NAS asks for one, instead of prompting for it")
+                )
+                .arg(
+                    Arg::new("TOTP_SECRET")
+                        .long("totp-secret")
+                        .action(ArgAction::SetTrue)
+                        .help("Prompt for the TOTP secret so that codes are generated without asking")
+                )
+                .arg(
+                    Arg::new("DEFAULT")
+                        .long("default")
+                        .action(ArgAction::SetTrue)
+                        .help("Use this user when no user is configured for the Synology NAS")
+                )
+                .arg(
+                    Arg::new("CA_BUNDLE")
+                        .long("ca-bundle")
+                        .help("A PEM file of CA certificates to trust for the Synology NAS")
+                )
+                .arg(
+                    Arg::new("CERTIFICATE_FINGERPRINT")
+                        .long("certificate-fingerprint")
+                        .help("The SHA-256 fingerprint of the Synology NAS certificate to trust")
+                )
         )
         .subcommand(
             Command::new("logout")
@@ -68,6 +145,116 @@ fn cli() -> Command {
                     .required(true)
                     .help("The URL for the Synology NAS")
             )
+            .arg(
+                Arg::new("USER")
+                    .short('u')
+                    .long("user")
+                    .help("Only delete the login for this user")
+            )
+        )
+        .subcommand(
+            Command::new("move")
+            .about("Moves the repository's store to another folder on the Synology NAS.")
+            .arg(
+                Arg::new("DESTINATION")
+                    .required(true)
+                    .help("The folder on the Synology NAS to move the store into")
+            )
+            .arg(
+                Arg::new("NAME")
+                    .long("name")
+                    .help("Rename the store to this name after moving it")
+            )
+            .arg(
+                Arg::new("ON_CONFLICT")
+                    .long("on-conflict")
+                    .value_parser(["error", "overwrite", "skip"])
+                    .default_value("error")
+                    .help("What to do with files that already exist in the destination")
+            )
+            .arg(
+                Arg::new("COPY")
+                    .long("copy")
+                    .action(ArgAction::SetTrue)
+                    .help("Copy the store instead of moving it")
+            )
+        )
+        .subcommand(
+            Command::new("search")
+            .about("Searches for objects in the repository's store on the Synology NAS.")
+            .arg(
+                Arg::new("PREFIX")
+                    .long("prefix")
+                    .conflicts_with("PATTERN")
+                    .help("Only match objects whose OID starts with this prefix")
+            )
+            .arg(
+                Arg::new("PATTERN")
+                    .long("pattern")
+                    .help("Only match objects whose name matches this glob pattern")
+            )
+            .arg(
+                Arg::new("MIN_SIZE")
+                    .long("min-size")
+                    .value_parser(value_parser!(u64))
+                    .help("Only match objects of at least this many bytes")
+            )
+            .arg(
+                Arg::new("MAX_SIZE")
+                    .long("max-size")
+                    .value_parser(value_parser!(u64))
+                    .help("Only match objects of at most this many bytes")
+            )
+            .arg(
+                Arg::new("MODIFIED_AFTER")
+                    .long("modified-after")
+                    .value_parser(value_parser!(u64))
+                    .help("Only match objects modified after this Unix timestamp")
+            )
+            .arg(
+                Arg::new("MODIFIED_BEFORE")
+                    .long("modified-before")
+                    .value_parser(value_parser!(u64))
+                    .help("Only match objects modified before this Unix timestamp")
+            )
+            .arg(
+                Arg::new("ALL")
+                    .short('a')
+                    .long("all")
+                    .action(ArgAction::SetTrue)
+                    .help("Search every repository on the share instead of only this repository")
+            )
+        )
+        .subcommand(
+            Command::new("share-link")
+            .about("Creates a public sharing link for a file tracked by Git LFS.")
+            .arg(
+                Arg::new("PATH")
+                    .required(true)
+                    .help("The path to the file in the working tree")
+            )
+            .arg(
+                Arg::new("EXPIRES")
+                    .long("expires")
+                    .help("The date the link expires, formatted as YYYY-MM-DD")
+            )
+            .arg(
+                Arg::new("MAX_ACCESS")
+                    .long("max-access")
+                    .value_parser(value_parser!(u32))
+                    .help("The number of times the link can be accessed")
+            )
+            .arg(
+                Arg::new("PASSWORD")
+                    .short('p')
+                    .long("password")
+                    .action(ArgAction::SetTrue)
+                    .help("Prompt for a password to protect the link")
+            )
+        )
+        .subcommand(
+            Command::new("usage")
+            .about("Reports how much space the repository's store uses on the Synology NAS.")
         )
 }
 
@@ -77,6 +264,12 @@ async fn main() -> Result<()> {
     let matches = cli().get_matches();
 
     let result: Result<()> = match matches.subcommand() {
+        Some(("credentials", sub_matches)) => {
+            let mut credentials_command = CredentialsSubcommand { };
+            credentials_command.execute(sub_matches).await?;
+
+            Ok(())
+        },
         Some(("login", sub_matches)) => {
             let mut login_command = LoginSubcommand { };
             login_command.execute(sub_matches).await?;
@@ -87,6 +280,30 @@ async fn main() -> Result<()> {
             let mut logout_command = LogoutSubcommand { };
             logout_command.execute(sub_matches).await?;
 
+            Ok(())
+        },
+        Some(("move", sub_matches)) => {
+            let mut move_command = MoveSubcommand { };
+            move_command.execute(sub_matches).await?;
+
+            Ok(())
+        },
+        Some(("search", sub_matches)) => {
+            let mut search_command = SearchSubcommand { };
+            search_command.execute(sub_matches).await?;
+
+            Ok(())
+        },
+        Some(("share-link", sub_matches)) => {
+            let mut share_link_command = ShareLinkSubcommand { };
+            share_link_command.execute(sub_matches).await?;
+
+            Ok(())
+        },
+        Some(("usage", sub_matches)) => {
+            let mut usage_command = UsageSubcommand { };
+            usage_command.execute(sub_matches).await?;
+
             Ok(())
         }
         _ => {
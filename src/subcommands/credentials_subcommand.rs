@@ -0,0 +1,180 @@
+use std::time::SystemTime;
+
+use anyhow::{bail, Context, Result};
+use clap::ArgMatches;
+
+use crate::configuration::Configuration;
+use crate::credential_manager::{CredentialManager, CredentialStore};
+use crate::synology_api::{ClientOptions, SynologyFileStation};
+
+use super::{nas_session::trust_stored_certificates, Subcommand};
+
+fn format_last_used(last_used: Option<SystemTime>) -> String {
+    let elapsed = match last_used.map(|last_used| SystemTime::now().duration_since(last_used)) {
+        None => return "never".to_string(),
+        Some(Err(_)) => return "just now".to_string(),
+        Some(Ok(elapsed)) => elapsed.as_secs()
+    };
+
+    match elapsed {
+        0..60 => "just now".to_string(),
+        60..3600 => format!("{} minute(s) ago", elapsed / 60),
+        3600..86400 => format!("{} hour(s) ago", elapsed / 3600),
+        _ => format!("{} day(s) ago", elapsed / 86400)
+    }
+}
+
+fn yes_no(value: bool) -> &'static str {
+    if value { "yes" } else { "no" }
+}
+
+#[derive(Debug)]
+pub struct CredentialsSubcommand {
+}
+
+impl CredentialsSubcommand {
+    #[tracing::instrument]
+    fn list(&self) -> Result<()> {
+        let credential_manager = CredentialManager::new()?;
+        let credentials = credential_manager.list_credentials()?;
+
+        if credentials.is_empty() {
+            println!("No credentials are stored.");
+
+            return Ok(());
+        }
+
+        println!("{:<40} {:<20} {:<8} {:<13} {:<12} LAST USED", "URL", "USER", "DEFAULT", "DEVICE TOKEN", "TOTP SECRET");
+        for credential in credentials {
+            println!(
+                "{:<40} {:<20} {:<8} {:<13} {:<12} {}",
+                credential.url,
+                credential.user,
+                yes_no(credential.is_default),
+                yes_no(credential.has_device_id),
+                yes_no(credential.has_totp_secret),
+                format_last_used(credential.last_used));
+        }
+
+        Ok(())
+    }
+
+    #[tracing::instrument]
+    async fn verify(&self, arg_matches: &ArgMatches) -> Result<()> {
+        let base_path = arg_matches.get_one::<String>("BASE_PATH").map(|base_path| Configuration::clean_base_path(base_path));
+
+        // Outside of a repository there is no configuration, so only the stored settings are used.
+        let configuration = Configuration::load().ok();
+
+        let mut credential_manager = CredentialManager::new()?;
+        let credentials = credential_manager.list_credentials()?;
+
+        let mut failures = 0;
+        for stored_credential in &credentials {
+            let result = async {
+                let credential = credential_manager.get_credential(&stored_credential.url, Some(&stored_credential.user))?
+                    .context("Credential should not be null")?;
+
+                // Proxy, timeouts and retries apply to every NAS, but certificates and the base path only to the configured one.
+                let configured_nas = configuration.as_ref().filter(|configuration| configuration.nas_url == stored_credential.url);
+                let mut options = match &configuration {
+                    Some(configuration) => configuration.client_options(),
+                    None => ClientOptions::default()
+                };
+                if configured_nas.is_none() {
+                    options.verify_certificate = true;
+                    options.ca_bundle = None;
+                    options.certificate_fingerprint = None;
+                }
+                trust_stored_certificates(&mut options, &credential);
+
+                let base_path = base_path.clone()
+                    .or(configured_nas.map(|configuration| configuration.base_path.clone()))
+                    .unwrap_or_default();
+                let mut file_station = SynologyFileStation::new(&format!("{}{}", stored_credential.url, base_path), options)?;
+
+                file_station.login(&credential, false, None).await?;
+                file_station.logout().await?;
+
+                anyhow::Ok(())
+            }.await;
+
+            match result {
+                Ok(_) => println!("PASS {} {}", stored_credential.url, stored_credential.user),
+                Err(error) => {
+                    failures += 1;
+                    println!("FAIL {} {}: {}", stored_credential.url, stored_credential.user, error);
+                }
+            }
+        }
+
+        if failures > 0 {
+            bail!("{} of {} credential(s) could not log in.", failures, credentials.len());
+        }
+
+        Ok(())
+    }
+
+    #[tracing::instrument]
+    fn prune(&self, arg_matches: &ArgMatches) -> Result<()> {
+        let dry_run = arg_matches.get_flag("DRY_RUN");
+
+        let mut credential_manager = CredentialManager::new()?;
+        let credentials = credential_manager.list_credentials()?;
+
+        let mut count = 0;
+        for credential in credentials {
+            if credential_manager.has_password(&credential.url, &credential.user)? {
+                continue;
+            }
+
+            println!("The password for {} at {} is missing.", credential.user, credential.url);
+            if !dry_run {
+                credential_manager.remove_credential(&credential.url, Some(&credential.user))?;
+            }
+
+            count += 1;
+        }
+
+        if dry_run {
+            println!("{} credential(s) would be removed.", count);
+        }
+        else {
+            println!("Removed {} credential(s).", count);
+        }
+
+        Ok(())
+    }
+
+    #[tracing::instrument]
+    fn migrate(&self, arg_matches: &ArgMatches) -> Result<()> {
+        let to = arg_matches.get_one::<String>("TO").context("TO not provided.")?.parse::<CredentialStore>()?;
+
+        let mut credential_manager = CredentialManager::new()?;
+        let from = credential_manager.store();
+        if from == to {
+            println!("Passwords are already kept in the {}.", to);
+
+            return Ok(());
+        }
+
+        let count = credential_manager.migrate(to)?;
+        println!("Moved {} password(s) from the {} to the {}.", count, from, to);
+        println!("Run \"git config --global synology.credentialstore {}\" to keep using the {}.", to, to);
+
+        Ok(())
+    }
+}
+
+impl Subcommand for CredentialsSubcommand {
+    #[tracing::instrument]
+    async fn execute(&mut self, arg_matches: &ArgMatches) -> Result<()> {
+        match arg_matches.subcommand() {
+            Some(("list", _)) => self.list(),
+            Some(("migrate", sub_matches)) => self.migrate(sub_matches),
+            Some(("prune", sub_matches)) => self.prune(sub_matches),
+            Some(("verify", sub_matches)) => self.verify(sub_matches).await,
+            _ => bail!("No credentials subcommand was given.")
+        }
+    }
+}
@@ -1,9 +1,11 @@
-use anyhow::{Context, Result};
+use anyhow::{bail, Context, Result};
 use clap::ArgMatches;
 use std::io::{self, Write};
+use std::path::PathBuf;
 
+use crate::configuration::Configuration;
 use crate::credential_manager::{Credential, CredentialManager};
-use crate::synology_api::{SynologyErrorStatus, SynologyFileStation};
+use crate::synology_api::{decode_totp_secret, display_fingerprint, normalize_fingerprint, ClientOptions, SynologyErrorStatus, SynologyFileStation};
 
 use super::Subcommand;
 
@@ -18,6 +20,19 @@ fn get_input(prompt: &str) -> Result<String>{
     Ok(input.trim().to_string())
 }
 
+fn read_password_stdin() -> Result<String> {
+    let mut password = String::new();
+    io::stdin().read_line(&mut password)?;
+
+    // Only remove the line ending, as the password may start or end with spaces.
+    let password = password.trim_end_matches(['\r', '\n']).to_string();
+    if password.is_empty() {
+        bail!("No password was given on stdin.");
+    }
+
+    Ok(password)
+}
+
 #[derive(Debug)]
 pub struct LoginSubcommand {
 }
@@ -30,29 +45,84 @@ impl Subcommand for LoginSubcommand {
 
         let mut credential_manager = CredentialManager::new()?;
 
-        let password: String;
-        let device_id: Option<String>;
-        if credential_manager.has_credential(url)? {
-            let credential = credential_manager.get_credential(url)?.context("Credential should not be null")?;
-            password = credential.password.clone();
-            device_id = credential.device_id;
+        let stored_credential = credential_manager.get_credential(url, Some(user))?;
+
+        // A password given on stdin replaces the stored one.
+        let password = if arg_matches.get_flag("PASSWORD_STDIN") {
+            read_password_stdin()?
         }
         else {
-            password = rpassword::prompt_password("Synology NAS Password: ")?;
-            device_id = None;
+            match &stored_credential {
+                Some(credential) => credential.password.clone(),
+                None => rpassword::prompt_password("Synology NAS Password: ")?
+            }
+        };
+
+        let device_id = stored_credential.as_ref().and_then(|credential| credential.device_id.clone());
+        let mut certificate_fingerprint = stored_credential.as_ref().and_then(|credential| credential.certificate_fingerprint.clone());
+        let ca_bundle = match arg_matches.get_one::<String>("CA_BUNDLE") {
+            Some(ca_bundle) => Some(PathBuf::from(ca_bundle)),
+            None => stored_credential.as_ref().and_then(|credential| credential.ca_bundle.clone())
+        };
+        let mut totp_secret = stored_credential.as_ref().and_then(|credential| credential.totp_secret.clone());
+
+        if arg_matches.get_flag("TOTP_SECRET") {
+            // The secret is read without echoing it, as it allows anyone to generate codes.
+            let secret = rpassword::prompt_password("TOTP Secret (base32): ")?;
+            decode_totp_secret(&secret)?;
+
+            totp_secret = Some(secret);
+        }
+
+        if let Some(fingerprint) = arg_matches.get_one::<String>("CERTIFICATE_FINGERPRINT") {
+            certificate_fingerprint = Some(normalize_fingerprint(fingerprint)?);
+        }
+
+        let base_path = Configuration::clean_base_path(
+            arg_matches.get_one::<String>("BASE_PATH").map(|base_path| base_path.as_str()).unwrap_or_default());
+        let dsm_url = format!("{}{}", url, base_path);
+
+        let mut options = ClientOptions {
+            ca_bundle: ca_bundle.clone(),
+            certificate_fingerprint: certificate_fingerprint.clone(),
+            ..Default::default()
+        };
+        let mut file_station = SynologyFileStation::new(&dsm_url, options.clone())?;
+
+        if certificate_fingerprint.is_none() {
+            // Trust on first use: ask before sending the password to a server whose certificate is not trusted.
+            if let Some(fingerprint) = file_station.probe_certificate().await? {
+                println!("The certificate presented by {} is not trusted.", url);
+                println!("SHA-256 fingerprint: {}", display_fingerprint(&fingerprint));
+
+                let answer = get_input("Trust this certificate? [y/N]: ")?;
+                if !answer.eq_ignore_ascii_case("y") && !answer.eq_ignore_ascii_case("yes") {
+                    bail!("The certificate was not trusted.");
+                }
+
+                options.certificate_fingerprint = Some(fingerprint.clone());
+                file_station = SynologyFileStation::new(&dsm_url, options)?;
+                certificate_fingerprint = Some(fingerprint);
+            }
         }
 
         let mut credential = Credential::new(
             user.clone(),
             password.clone());
         credential.device_id = device_id;
+        credential.certificate_fingerprint = certificate_fingerprint;
+        credential.ca_bundle = ca_bundle;
+        credential.totp_secret = totp_secret;
 
-        let mut file_station = SynologyFileStation::new(url);
+        // With a stored TOTP secret, login generates the code itself instead of returning NoTotp.
         let credential = match file_station.login(&credential, false, None).await {
             Ok(credential) => Ok(credential),
             Err(error) => match error {
                 SynologyErrorStatus::NoTotp => {
-                    let totp = get_input("TOTP: ")?;
+                    let totp = match arg_matches.get_one::<String>("TOTP") {
+                        Some(totp) => totp.to_string(),
+                        None => get_input("TOTP: ")?
+                    };
                     
                     file_station.login(&credential, true, Some(totp)).await
                 },
@@ -62,6 +132,10 @@ impl Subcommand for LoginSubcommand {
         
         credential_manager.set_credential(url, &credential)?;
 
+        if arg_matches.get_flag("DEFAULT") {
+            credential_manager.set_default_user(url, user)?;
+        }
+
         Ok(())
     }
 }
\ No newline at end of file
@@ -13,8 +13,10 @@ impl Subcommand for LogoutSubcommand {
     async fn execute(&mut self, arg_matches: &ArgMatches) -> Result<()> {
         let url = arg_matches.get_one::<String>("URL").context("URL not provided.")?;
 
+        let user = arg_matches.get_one::<String>("USER");
+
         let mut credential_manager = CredentialManager::new()?;
-        credential_manager.remove_credential(url)?;
+        credential_manager.remove_credential(url, user.map(|user| user.as_str()))?;
 
         Ok(())
     }
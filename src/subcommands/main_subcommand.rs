@@ -1,15 +1,20 @@
-use std::{fs::{exists, File}, path::{Path, PathBuf}};
+use std::{fs::{exists, File}, path::{Path, PathBuf}, process, time::Duration};
 
-use anyhow::{Context, Result};
+use anyhow::{bail, Context, Result};
 use clap::ArgMatches;
-use gix_discover::repository;
+use futures_util::TryStreamExt;
 use named_lock::NamedLock;
-use tokio::fs::remove_file;
-use tracing::info;
+use tokio::{fs::remove_file, signal};
+use tracing::{info, warn};
 
-use crate::{configuration::Configuration, credential_manager::CredentialManager, git_lfs::{error_init, CustomTransferAgent, Event, GitLfsParser, GitLfsProgressReporter}, synology_api::{ProgressReporter, SynologyFileStation}, users_dirs::get_cache_dir};
+use crate::{configuration::Configuration, git_lfs::{error_init, CustomTransferAgent, Event, GitLfsParser, GitLfsProgressReporter}, synology_api::{AdditionalInfo, ProgressReporter, SynologyErrorStatus, SynologyFileStation, SynologyStatusCode}, users_dirs::get_cache_dir};
 
-use super::Subcommand;
+use super::{nas_session::{abandon_session, end_session, get_existing_paths, get_share_path, login_with_stored_credential}, Subcommand};
+
+const LIST_PAGE_SIZE: u64 = 1000;
+const TASK_POLL_INTERVAL: Duration = Duration::from_secs(1);
+const UPLOAD_VERIFY_ATTEMPTS: u32 = 2;
+const PERMISSION_CHECK_FILE_NAME: &str = "git-lfs-synology-permission-check";
 
 #[derive(Debug)]
 struct StdOutProgressReporter {
@@ -88,22 +93,18 @@ impl CustomTransferAgent for MainSubcommand {
     }
 
     #[tracing::instrument]
-    async fn init(&mut self, _: &Event) -> Result<()> {
+    async fn init(&mut self, event: &Event) -> Result<()> {
         let configuration = Configuration::load()?;
-        let mut credential_manager = CredentialManager::new()?;
-
-        let nas_url = configuration.nas_url.as_str();
-        let mut file_station = SynologyFileStation::new(nas_url);
 
-        let credential = credential_manager.get_credential(nas_url)?.context("Credential should not be null")?;
-        match file_station.login(&credential, false, None).await {
-            Ok(_) => Ok(()),
+        let file_station = match login_with_stored_credential(&configuration).await {
+            Ok(file_station) => Ok(file_station),
             Err(error) => {
                 error_init(1, error.to_string().as_str())?;
                 Err(error)
             }
         }?;
 
+        self.spawn_interrupt_handler(file_station.clone(), configuration.clone());
         self.file_station = Some(file_station);
         
         match self.create_target_folder().await {
@@ -112,12 +113,34 @@ impl CustomTransferAgent for MainSubcommand {
                 error_init(1, error.to_string().as_str())?;
                 Err(error)
             }
+        }?;
+
+        if event.operation.as_deref() == Some("upload") {
+            match self.check_write_permission(&configuration).await {
+                Ok(_) => Ok(()),
+                Err(error) => {
+                    error_init(1, error.to_string().as_str())?;
+                    Err(error)
+                }
+            }?;
         }
+
+        Ok(())
     }
 
     #[tracing::instrument]
     async fn terminate(&mut self) -> Result<()> {
-        // No cleanup to do.
+        // Cleanup is best effort, as every transfer has already completed.
+        if let Some(mut file_station) = self.file_station.take() {
+            let result = match Configuration::load() {
+                Ok(configuration) => end_session(&mut file_station, &configuration).await,
+                Err(error) => Err(error)
+            };
+
+            if let Err(error) = result {
+                warn!("An error occurred ending the session: \"{error}\".");
+            }
+        }
 
         Ok(())
     }
@@ -161,7 +184,17 @@ impl CustomTransferAgent for MainSubcommand {
 
         let source_path = Path::new(&compressed_source_path);
         let file_station = self.file_station.clone().context("File Station should not be null")?;
-        file_station.upload(source_path, event.size.context("Size should not be null")?, configuration.path.as_str(), false, false, None, None, None, Some(progress_reporter)).await?;
+        let local_md5 = file_station.upload(source_path, event.size.context("Size should not be null")?, configuration.path.as_str(), false, false, None, None, None, Some(progress_reporter)).await?;
+
+        if configuration.verify_upload {
+            let remote_path = format!(
+                "{}/{}",
+                configuration.path,
+                source_path.file_name().context("File name should not be null")?.to_string_lossy()
+            );
+
+            self.verify_upload(source_path, remote_path.as_str(), local_md5, event.size.context("Size should not be null")?, configuration.path.as_str()).await?;
+        }
 
         // Remove the path if the compressed source path is not the same as the source path provided by git lfs.
         if event_source_path != compressed_source_path {
@@ -178,9 +211,14 @@ impl Subcommand for MainSubcommand {
     #[tracing::instrument]
     async fn execute(&mut self, arg_matches: &ArgMatches) -> Result<()> {
         let mut parser = GitLfsParser::<MainSubcommand>::new(self);
-        parser.listen().await?;
+        let result = parser.listen().await;
 
-        Ok(())
+        if result.is_err() {
+            warn!("The transfer agent failed, logging out before exiting.");
+            self.logout().await;
+        }
+
+        result
     }
 }
 
@@ -192,6 +230,35 @@ impl MainSubcommand {
         }
     }
 
+    #[tracing::instrument]
+    async fn check_write_permission(&self, configuration: &Configuration) -> Result<()> {
+        let file_station = self.file_station.clone().context("File Station should not be null")?;
+
+        match file_station.check_write_permission(&configuration.path, PERMISSION_CHECK_FILE_NAME, false, true).await {
+            Ok(_) => {
+                info!("User can write to \"{}\".", configuration.path);
+
+                Ok(())
+            },
+            Err(SynologyErrorStatus::ServerError(code)) if matches!(code,
+                SynologyStatusCode::LoggedInSessionDoesNotHavePermission |
+                SynologyStatusCode::InvalidUserDoesThisFileOperation |
+                SynologyStatusCode::InvalidGroupDoesThisFileOperation |
+                SynologyStatusCode::InvalidUserAndGroupDoesThisFileOperation |
+                SynologyStatusCode::OperationNotPermitted) => {
+                let share = get_share_path(&configuration.path).unwrap_or(configuration.path.clone());
+
+                bail!(
+                    "User \"{}\" does not have permission to write to the share \"{}\": {}.",
+                    file_station.user().unwrap_or_default(),
+                    share,
+                    code
+                )
+            },
+            Err(error) => Err(error.into())
+        }
+    }
+
     #[tracing::instrument]
     async fn compress_file(&self, path: &str) -> Result<String> {
         let source_file = Path::new(path);
@@ -263,28 +330,95 @@ impl MainSubcommand {
         if self.is_path_root(&parent) {
             info!("Parent is root, let's get shares.");
 
-            let shares = file_station.list_share(
-                None, None, None, None, None,
-                false, false, false, false, false, false, false).await?;
+            let exists = file_station
+                .list_share_stream(LIST_PAGE_SIZE, AdditionalInfo::new())
+                .try_any(|share| {
+                    let found = share.name == name;
+                    async move { found }
+                })
+                .await?;
 
-            return Ok(shares.shares.iter().any(|share| share.name == name));
+            return Ok(exists);
         }
         else {
-            info!("Parent is not root, let's get files.");
-
-            let files = file_station.list(
-                &parent, None, None, None, None, None, None, None,
-                false, false, false, false, false, false, false).await?;
+            info!("Parent is not root, let's get the file info.");
 
-            return Ok(files.files.iter().any(|file| file.name == name));
+            return self.any_exists_on_remote(&[path]).await;
         }
     }
 
+    #[tracing::instrument]
+    async fn any_exists_on_remote(&self, paths: &[&str]) -> Result<bool> {
+        let file_station = self.file_station.clone().context("File Station should not be null")?;
+
+        Ok(!get_existing_paths(&file_station, paths).await?.is_empty())
+    }
+
     #[tracing::instrument]
     async fn exists_on_remote_compressed_or_uncompressed(&self, path: &str) -> Result<bool> {
         let compressed_path = format!("{}.zstd", path);
 
-        Ok(self.exists_on_remote(path).await? || self.exists_on_remote(&compressed_path).await?)
+        self.any_exists_on_remote(&[path, compressed_path.as_str()]).await
+    }
+
+    #[tracing::instrument]
+    async fn verify_upload(&self, source_path: &Path, remote_path: &str, local_md5: String, size: usize, target_directory_path: &str) -> Result<()> {
+        let file_station = self.file_station.clone().context("File Station should not be null")?;
+        let mut local_md5 = local_md5;
+
+        for attempt in 1..=UPLOAD_VERIFY_ATTEMPTS {
+            info!("Verifying upload of \"{}\", attempt {}.", remote_path, attempt);
+            let remote_md5 = file_station.md5(remote_path, TASK_POLL_INTERVAL).await?;
+
+            if remote_md5.eq_ignore_ascii_case(&local_md5) {
+                info!("Server MD5 matches the local MD5.");
+
+                return Ok(());
+            }
+
+            warn!("Server MD5 \"{}\" does not match local MD5 \"{}\". Deleting the remote object.", remote_md5, local_md5);
+            file_station.delete(&[remote_path], false).await?;
+
+            if attempt < UPLOAD_VERIFY_ATTEMPTS {
+                info!("Uploading the object again.");
+                local_md5 = file_station.upload::<StdOutProgressReporter>(source_path, size, target_directory_path, false, true, None, None, None, None).await?;
+            }
+        }
+
+        bail!("The uploaded object \"{}\" did not match the local file.", remote_path)
+    }
+
+    #[tracing::instrument]
+    async fn logout(&mut self) {
+        // Cleanup is best effort so that it never hides the original error.
+        if let Some(mut file_station) = self.file_station.take() {
+            info!("Logging out of the Synology NAS.");
+
+            let result = match Configuration::load() {
+                Ok(configuration) => abandon_session(&mut file_station, &configuration).await,
+                Err(error) => Err(error)
+            };
+
+            if let Err(error) = result {
+                warn!("An error occurred logging out: \"{error}\".");
+            }
+        }
+    }
+
+    #[tracing::instrument]
+    fn spawn_interrupt_handler(&self, mut file_station: SynologyFileStation, configuration: Configuration) {
+        // The parser blocks while reading stdin, so listen for Ctrl-C on another task.
+        tokio::spawn(async move {
+            if signal::ctrl_c().await.is_ok() {
+                warn!("Interrupted, ending the session before exiting.");
+
+                if let Err(error) = abandon_session(&mut file_station, &configuration).await {
+                    warn!("An error occurred logging out: \"{error}\".");
+                }
+
+                process::exit(130);
+            }
+        });
     }
 
     #[tracing::instrument]
@@ -1,9 +1,20 @@
+mod credentials_subcommand;
 mod login_subcommand;
 mod logout_subcommand;
 mod main_subcommand;
+mod move_subcommand;
+mod nas_session;
+mod search_subcommand;
+mod share_link_subcommand;
 mod subcommand;
+mod usage_subcommand;
 
+pub use credentials_subcommand::CredentialsSubcommand;
 pub use login_subcommand::LoginSubcommand;
 pub use logout_subcommand::LogoutSubcommand;
 pub use main_subcommand::MainSubcommand;
+pub use move_subcommand::MoveSubcommand;
+pub use search_subcommand::SearchSubcommand;
+pub use share_link_subcommand::ShareLinkSubcommand;
 pub use subcommand::Subcommand;
+pub use usage_subcommand::UsageSubcommand;
@@ -0,0 +1,58 @@
+use std::time::Duration;
+
+use anyhow::{anyhow, Context, Result};
+use clap::ArgMatches;
+
+use crate::{configuration::Configuration, synology_api::ConflictPolicy};
+
+use super::{nas_session::login_with_stored_credential, Subcommand};
+
+const TASK_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Debug)]
+pub struct MoveSubcommand {
+}
+
+impl Subcommand for MoveSubcommand {
+    #[tracing::instrument]
+    async fn execute(&mut self, arg_matches: &ArgMatches) -> Result<()> {
+        let destination = arg_matches.get_one::<String>("DESTINATION").context("DESTINATION not provided.")?;
+        let destination = Configuration::clean_base_path(destination);
+        let name = arg_matches.get_one::<String>("NAME");
+        let copy = arg_matches.get_flag("COPY");
+
+        let conflict_policy = match arg_matches.get_one::<String>("ON_CONFLICT").map(|value| value.as_str()) {
+            Some("overwrite") => Ok(ConflictPolicy::Overwrite),
+            Some("skip") => Ok(ConflictPolicy::Skip),
+            Some("error") | None => Ok(ConflictPolicy::Error),
+            Some(value) => Err(anyhow!("\"{}\" is not a conflict policy.", value))
+        }?;
+
+        let configuration = Configuration::load()?;
+        let store_name = configuration.path
+            .rsplit('/')
+            .find(|part| !part.is_empty())
+            .context("Path should include a folder.")?
+            .to_string();
+
+        let file_station = login_with_stored_credential(&configuration).await?;
+
+        file_station.copy_move(&[configuration.path.as_str()], &destination, conflict_policy, !copy, TASK_POLL_INTERVAL).await?;
+        let mut new_path = format!("{}/{}", destination, store_name);
+
+        if let Some(name) = name {
+            file_station.rename(&[new_path.as_str()], &[name.as_str()]).await?;
+            new_path = format!("{}/{}", destination, name);
+        }
+
+        if copy {
+            println!("Copied \"{}\" to \"{}\".", configuration.path, new_path);
+        }
+        else {
+            println!("Moved \"{}\" to \"{}\".", configuration.path, new_path);
+        }
+        println!("Update lfs.url in .lfsconfig to use the new path.");
+
+        Ok(())
+    }
+}
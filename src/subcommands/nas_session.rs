@@ -0,0 +1,194 @@
+use anyhow::{Context, Result};
+use named_lock::NamedLock;
+use tracing::info;
+
+use crate::{configuration::Configuration, credential_manager::{Credential, CredentialManager}, synology_api::{AdditionalInfo, ClientOptions, SynologyErrorStatus, SynologyFileStation, SynologyStatusCode}};
+
+const SESSION_LOCK_NAME: &str = "git-lfs-synology::nas_session::session";
+
+#[tracing::instrument]
+pub async fn login_with_stored_credential(configuration: &Configuration) -> Result<SynologyFileStation> {
+    let mut credential_manager = CredentialManager::new()?;
+
+    let nas_url = configuration.nas_url.as_str();
+    let credential = get_credential(&mut credential_manager, configuration)?;
+
+    let mut options = configuration.client_options();
+    trust_stored_certificates(&mut options, &credential);
+
+    let mut file_station = SynologyFileStation::new(&configuration.dsm_url(), options)?;
+
+    if configuration.session_cache {
+        if let Some(sid) = credential_manager.get_session(nas_url, &credential)? {
+            match file_station.resume_session(&credential, &sid).await {
+                Ok(_) => {
+                    info!("Reusing the cached session.");
+                    credential_manager.set_last_used(nas_url, &credential.user)?;
+
+                    return Ok(file_station);
+                },
+                Err(_) => {
+                    info!("Cached session is no longer valid, logging in.");
+                    credential_manager.remove_session(nas_url, &credential.user)?;
+                }
+            }
+        }
+    }
+
+    file_station.login(&credential, false, None).await?;
+    credential_manager.set_last_used(nas_url, &credential.user)?;
+
+    if configuration.session_cache {
+        credential_manager.set_session(nas_url, &credential, &file_station.session_id().context("Session should not be null")?, configuration.session_lifetime())?;
+    }
+
+    Ok(file_station)
+}
+
+/// A fingerprint or CA bundle in the configuration takes precedence over one given at login.
+#[tracing::instrument]
+pub fn trust_stored_certificates(options: &mut ClientOptions, credential: &Credential) {
+    if options.certificate_fingerprint.is_none() {
+        options.certificate_fingerprint = credential.certificate_fingerprint.clone();
+    }
+
+    if options.ca_bundle.is_none() {
+        options.ca_bundle = credential.ca_bundle.clone();
+    }
+}
+
+/// Returns the credential from the environment if it is set there, otherwise from the credential store.
+#[tracing::instrument]
+fn get_credential(credential_manager: &mut CredentialManager, configuration: &Configuration) -> Result<Credential> {
+    match Credential::from_environment() {
+        Some(credential) => Ok(credential),
+        None => credential_manager.get_credential(&configuration.nas_url, configuration.user.as_deref())?
+            .with_context(|| match &configuration.user {
+                Some(user) => format!("No credential is stored for \"{}\". Log in as that user first.", user),
+                None => "No credential is stored. Log in first.".to_string()
+            })
+    }
+}
+
+/// Keeps the session for the next process, renewing its expiry, or logs out if sessions are not cached.
+#[tracing::instrument]
+pub async fn end_session(file_station: &mut SynologyFileStation, configuration: &Configuration) -> Result<()> {
+    if !configuration.session_cache {
+        file_station.logout().await?;
+
+        return Ok(());
+    }
+
+    // The session may have been renewed by logging in again, so store the current one.
+    let mut credential_manager = CredentialManager::new()?;
+    let credential = get_credential(&mut credential_manager, configuration)?;
+    let sid = file_station.session_id().context("Session should not be null")?;
+
+    let keep_session = {
+        // git-lfs runs several agents at once, so only one of them may replace the cached session.
+        let lock = NamedLock::create(SESSION_LOCK_NAME)?;
+        let _guard = lock.lock()?;
+
+        match credential_manager.get_session(&configuration.nas_url, &credential)? {
+            Some(cached_sid) if cached_sid != sid => false,
+            _ => {
+                info!("Keeping the session for the next process.");
+                credential_manager.set_session(&configuration.nas_url, &credential, &sid, configuration.session_lifetime())?;
+
+                true
+            }
+        }
+    };
+
+    if !keep_session {
+        // Replacing the cached session would leave it logged in with nothing to log it out.
+        info!("Another process cached its session, logging out of this one.");
+        file_station.logout().await?;
+    }
+
+    Ok(())
+}
+
+/// Ends the session of a process that stops early. A session shared with other agents through the cache is left
+/// for them, as logging it out would interrupt their transfers.
+#[tracing::instrument]
+pub async fn abandon_session(file_station: &mut SynologyFileStation, configuration: &Configuration) -> Result<()> {
+    if configuration.session_cache && is_cached_session(file_station, configuration)? {
+        info!("Leaving the cached session for the other agents.");
+
+        return Ok(());
+    }
+
+    file_station.logout().await?;
+
+    Ok(())
+}
+
+#[tracing::instrument]
+fn is_cached_session(file_station: &SynologyFileStation, configuration: &Configuration) -> Result<bool> {
+    let sid = match file_station.session_id() {
+        Some(sid) => sid,
+        None => return Ok(false)
+    };
+
+    let mut credential_manager = CredentialManager::new()?;
+    let credential = get_credential(&mut credential_manager, configuration)?;
+
+    let lock = NamedLock::create(SESSION_LOCK_NAME)?;
+    let _guard = lock.lock()?;
+
+    Ok(credential_manager.get_session(&configuration.nas_url, &credential)?.is_some_and(|cached_sid| cached_sid == sid))
+}
+
+#[tracing::instrument]
+pub fn get_share_path(path: &str) -> Option<String> {
+    path.split('/')
+        .find(|part| !part.is_empty())
+        .map(|share| format!("/{}", share))
+}
+
+#[tracing::instrument]
+pub fn parse_object_name(name: &str) -> (String, bool) {
+    match name.strip_suffix(".zstd") {
+        Some(oid) => (oid.to_string(), true),
+        None => (name.to_string(), false)
+    }
+}
+
+#[tracing::instrument]
+pub async fn get_existing_paths(file_station: &SynologyFileStation, paths: &[&str]) -> Result<Vec<String>> {
+    match file_station.get_info(paths, AdditionalInfo::new()).await {
+        Ok(info) => Ok(
+            info.files
+                .into_iter()
+                .filter(|file| file.exists())
+                .map(|file| file.path)
+                .collect()
+        ),
+        Err(SynologyErrorStatus::ServerError(SynologyStatusCode::NoSuchFileOrDirectory)) => {
+            if paths.len() == 1 {
+                return Ok(vec![]);
+            }
+
+            // The whole request failed, so check each path on its own.
+            info!("Request for multiple paths failed, checking each path.");
+            let mut existing_paths = Vec::<String>::new();
+            for path in paths {
+                existing_paths.extend(Box::pin(get_existing_paths(file_station, &[path])).await?);
+            }
+
+            Ok(existing_paths)
+        },
+        Err(error) => Err(error.into())
+    }
+}
+
+#[tracing::instrument]
+pub async fn find_remote_object(file_station: &SynologyFileStation, configuration: &Configuration, oid: &str) -> Result<Option<String>> {
+    let path = format!("{}/{}", configuration.path, oid);
+    let compressed_path = format!("{}.zstd", path);
+
+    let existing_paths = get_existing_paths(file_station, &[path.as_str(), compressed_path.as_str()]).await?;
+
+    Ok(existing_paths.into_iter().next())
+}
@@ -0,0 +1,64 @@
+use std::time::Duration;
+
+use anyhow::Result;
+use clap::ArgMatches;
+
+use crate::{configuration::Configuration, synology_api::{AdditionalInfo, SearchCriteria}};
+
+use super::{nas_session::{get_share_path, login_with_stored_credential, parse_object_name}, Subcommand};
+
+const SEARCH_PAGE_SIZE: u64 = 1000;
+const TASK_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Debug)]
+pub struct SearchSubcommand {
+}
+
+impl Subcommand for SearchSubcommand {
+    #[tracing::instrument]
+    async fn execute(&mut self, arg_matches: &ArgMatches) -> Result<()> {
+        let configuration = Configuration::load()?;
+        let file_station = login_with_stored_credential(&configuration).await?;
+
+        let folder_path = if arg_matches.get_flag("ALL") {
+            get_share_path(&configuration.path).unwrap_or(configuration.path.clone())
+        }
+        else {
+            configuration.path.clone()
+        };
+
+        let pattern = match arg_matches.get_one::<String>("PREFIX") {
+            Some(prefix) => Some(format!("{}*", prefix)),
+            None => arg_matches.get_one::<String>("PATTERN").cloned()
+        };
+
+        let criteria = SearchCriteria {
+            folder_paths: vec![folder_path],
+            recursive: true,
+            pattern,
+            file_type: Some("file".to_string()),
+            size_from: arg_matches.get_one::<u64>("MIN_SIZE").copied(),
+            size_to: arg_matches.get_one::<u64>("MAX_SIZE").copied(),
+            mtime_from: arg_matches.get_one::<u64>("MODIFIED_AFTER").copied(),
+            mtime_to: arg_matches.get_one::<u64>("MODIFIED_BEFORE").copied(),
+            ..Default::default()
+        };
+
+        let files = file_station.search(&criteria, SEARCH_PAGE_SIZE, AdditionalInfo::new().size(), TASK_POLL_INTERVAL).await?;
+
+        for file in files {
+            let (oid, compressed) = parse_object_name(&file.name);
+            let size = file.additional.and_then(|additional| additional.size).unwrap_or(0);
+
+            println!(
+                "{}\t{}\t{}\t{}",
+                oid,
+                size,
+                if compressed { "compressed" } else { "uncompressed" },
+                file.path
+            );
+        }
+
+        Ok(())
+    }
+}
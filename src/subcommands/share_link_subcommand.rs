@@ -0,0 +1,48 @@
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use clap::ArgMatches;
+
+use crate::{configuration::Configuration, git_lfs::read_oid, synology_api::SharingLinkOptions};
+
+use super::{nas_session::{find_remote_object, login_with_stored_credential}, Subcommand};
+
+#[derive(Debug)]
+pub struct ShareLinkSubcommand {
+}
+
+impl Subcommand for ShareLinkSubcommand {
+    #[tracing::instrument]
+    async fn execute(&mut self, arg_matches: &ArgMatches) -> Result<()> {
+        let path = arg_matches.get_one::<String>("PATH").context("PATH not provided.")?;
+        let oid = read_oid(Path::new(path))?;
+
+        let configuration = Configuration::load()?;
+        let file_station = login_with_stored_credential(&configuration).await?;
+
+        let remote_path = find_remote_object(&file_station, &configuration, &oid)
+            .await?
+            .with_context(|| format!("Object \"{}\" does not exist on the Synology NAS.", oid))?;
+
+        let password = if arg_matches.get_flag("PASSWORD") {
+            Some(rpassword::prompt_password("Sharing Link Password: ")?)
+        }
+        else {
+            None
+        };
+
+        let options = SharingLinkOptions {
+            password,
+            date_expired: arg_matches.get_one::<String>("EXPIRES").cloned(),
+            date_available: None,
+            expire_times: arg_matches.get_one::<u32>("MAX_ACCESS").copied()
+        };
+
+        let response = file_station.sharing_create(&[remote_path.as_str()], &options).await?;
+        let link = response.links.first().context("Server should return a sharing link.")?;
+
+        println!("{}", link.url);
+
+        Ok(())
+    }
+}
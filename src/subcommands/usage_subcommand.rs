@@ -0,0 +1,70 @@
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use clap::ArgMatches;
+use futures_util::TryStreamExt;
+
+use crate::{configuration::Configuration, synology_api::{AdditionalInfo, SharedFolder}};
+
+use super::{nas_session::{get_share_path, login_with_stored_credential, parse_object_name}, Subcommand};
+
+const LIST_PAGE_SIZE: u64 = 1000;
+const TASK_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Debug)]
+pub struct UsageSubcommand {
+}
+
+impl Subcommand for UsageSubcommand {
+    #[tracing::instrument]
+    async fn execute(&mut self, _: &ArgMatches) -> Result<()> {
+        let configuration = Configuration::load()?;
+        let file_station = login_with_stored_credential(&configuration).await?;
+
+        let dir_size = file_station.dir_size(&[configuration.path.as_str()], TASK_POLL_INTERVAL).await?;
+
+        let (compressed, uncompressed) = file_station
+            .list_stream(&configuration.path, LIST_PAGE_SIZE, None, Some("file".to_string()), AdditionalInfo::new())
+            .try_fold((0u64, 0u64), |(compressed, uncompressed), file| async move {
+                if parse_object_name(&file.name).1 {
+                    Ok((compressed + 1, uncompressed))
+                }
+                else {
+                    Ok((compressed, uncompressed + 1))
+                }
+            })
+            .await?;
+
+        println!("Path: {}", configuration.path);
+        println!("Total size: {} bytes", dir_size.total_size);
+        println!("Objects: {} ({} compressed, {} uncompressed)", dir_size.num_file, compressed, uncompressed);
+
+        let share_path = get_share_path(&configuration.path).context("Path should include a share.")?;
+        let shares: Vec<SharedFolder> = file_station
+            .list_share_stream(LIST_PAGE_SIZE, AdditionalInfo::new().volume_status())
+            .try_collect()
+            .await?;
+        let volume_status = shares
+            .into_iter()
+            .find(|share| share.path == share_path)
+            .and_then(|share| share.additional)
+            .and_then(|additional| additional.volume_status);
+
+        match volume_status {
+            Some(volume_status) => {
+                let percentage = if volume_status.totalspace > 0 {
+                    dir_size.total_size as f64 / volume_status.totalspace as f64 * 100.0
+                }
+                else {
+                    0.0
+                };
+
+                println!("Share free space: {} of {} bytes", volume_status.freespace, volume_status.totalspace);
+                println!("This repository uses {:.2}% of the volume.", percentage);
+            },
+            None => println!("Share free space: unknown")
+        }
+
+        Ok(())
+    }
+}
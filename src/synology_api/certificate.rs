@@ -0,0 +1,118 @@
+use std::sync::Arc;
+
+use reqwest::tls::TlsInfo;
+use rustls::{client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier}, crypto::{ring, verify_tls12_signature, verify_tls13_signature, CryptoProvider}, pki_types::{CertificateDer, ServerName, UnixTime}, ClientConfig, DigitallySignedStruct, Error, SignatureScheme};
+use sha2::{Digest, Sha256};
+use tracing::{info, warn};
+
+use super::SynologyErrorStatus;
+
+/// Normalizes a SHA-256 fingerprint to lowercase hex without separators.
+#[tracing::instrument]
+pub fn normalize_fingerprint(fingerprint: &str) -> Result<String, SynologyErrorStatus> {
+    let normalized = fingerprint
+        .chars()
+        .filter(|character| *character != ':' && !character.is_whitespace())
+        .collect::<String>()
+        .to_lowercase();
+
+    if normalized.len() != 64 || !normalized.chars().all(|character| character.is_ascii_hexdigit()) {
+        warn!("\"{}\" is not a SHA-256 fingerprint.", fingerprint);
+
+        return Err(SynologyErrorStatus::InvalidCertificateFingerprint(fingerprint.to_string()));
+    }
+
+    Ok(normalized)
+}
+
+/// Formats a normalized fingerprint the way browsers and `openssl x509 -fingerprint` show it.
+#[tracing::instrument]
+pub fn display_fingerprint(fingerprint: &str) -> String {
+    fingerprint
+        .as_bytes()
+        .chunks(2)
+        .map(|pair| String::from_utf8_lossy(pair).to_uppercase())
+        .collect::<Vec<String>>()
+        .join(":")
+}
+
+#[tracing::instrument(skip(certificate))]
+pub fn certificate_fingerprint(certificate: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(certificate))
+}
+
+#[tracing::instrument(skip(tls_info))]
+pub fn peer_fingerprint(tls_info: &TlsInfo) -> Option<String> {
+    tls_info.peer_certificate().map(certificate_fingerprint)
+}
+
+/// Builds a TLS configuration that only trusts a server presenting the certificate with the given fingerprint.
+#[tracing::instrument]
+pub fn pinned_tls_config(fingerprint: &str) -> Result<ClientConfig, SynologyErrorStatus> {
+    let verifier = PinnedCertificateVerifier {
+        fingerprint: normalize_fingerprint(fingerprint)?,
+        provider: Arc::new(ring::default_provider())
+    };
+
+    let mut config = ClientConfig::builder_with_provider(verifier.provider.clone())
+        .with_safe_default_protocol_versions()
+        .map_err(|error| SynologyErrorStatus::TlsError(error.to_string()))?
+        .dangerous()
+        .with_custom_certificate_verifier(Arc::new(verifier))
+        .with_no_client_auth();
+
+    // reqwest only negotiates ALPN for the TLS configurations it builds itself.
+    config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+
+    Ok(config)
+}
+
+/// Accepts only the pinned certificate. The chain and host name are not checked, as DSM's self-signed certificates would fail both.
+#[derive(Debug)]
+struct PinnedCertificateVerifier {
+    fingerprint: String,
+    provider: Arc<CryptoProvider>
+}
+
+impl ServerCertVerifier for PinnedCertificateVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        _intermediates: &[CertificateDer<'_>],
+        _server_name: &ServerName<'_>,
+        _ocsp_response: &[u8],
+        _now: UnixTime) -> Result<ServerCertVerified, Error> {
+            let fingerprint = certificate_fingerprint(end_entity.as_ref());
+
+            if fingerprint == self.fingerprint {
+                info!("Server certificate matches the pinned fingerprint.");
+
+                Ok(ServerCertVerified::assertion())
+            }
+            else {
+                warn!("Server certificate \"{}\" does not match the pinned fingerprint.", display_fingerprint(&fingerprint));
+
+                Err(Error::General("The server certificate does not match the pinned fingerprint.".to_string()))
+            }
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct) -> Result<HandshakeSignatureValid, Error> {
+            verify_tls12_signature(message, cert, dss, &self.provider.signature_verification_algorithms)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct) -> Result<HandshakeSignatureValid, Error> {
+            verify_tls13_signature(message, cert, dss, &self.provider.signature_verification_algorithms)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.provider.signature_verification_algorithms.supported_schemes()
+    }
+}
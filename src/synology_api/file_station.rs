@@ -1,39 +1,263 @@
-use std::{collections::HashMap, path::{Path, PathBuf}, time::Duration};
+use std::{collections::HashMap, future::Future, io, path::{Path, PathBuf}, sync::{atomic::{AtomicBool, AtomicU64, Ordering}, Arc, Mutex, RwLock}, time::{Duration, Instant, SystemTime}};
 
+use bytes::Bytes;
+use futures_util::{future::try_join_all, stream, Stream, TryStreamExt};
+use md5::{Digest, Md5};
 use num_traits::FromPrimitive;
-use reqwest::{Error, Response, StatusCode};
+use reqwest::{header::RANGE, tls::TlsInfo, Body, Certificate, Client, Error, Proxy, Response, StatusCode};
 use serde::de::DeserializeOwned;
-use tokio::{fs::File, io::AsyncWriteExt};
+use serde_json::Value;
+use tokio::{fs, io::AsyncWriteExt, time::{sleep, timeout}};
+use tokio_util::io::ReaderStream;
 use tracing::{info, warn};
 use urlencoding::encode;
 
 use crate::credential_manager::Credential;
 
-use super::{responses::{CreateFolderResponse, ListResponse, ListShareResponse, LoginError, LoginResponse, SynologyError, SynologyErrorStatus, SynologyResult, SynologyStatusCode}, ProgressReporter};
+use super::{certificate::{peer_fingerprint, pinned_tls_config}, options::{AdditionalInfo, ClientOptions, ConflictPolicy, SearchCriteria, SharingLinkOptions}, partial_download::{DownloadState, PartialDownload, SegmentState}, responses::{ApiInfo, CopyMoveStatusResponse, CreateFolderResponse, CreateSharingLinkResponse, DeleteStatusResponse, DirSizeStatusResponse, File, GetInfoResponse, ListResponse, ListSharingLinkResponse, ListShareResponse, LoginError, LoginResponse, Md5StatusResponse, RenameResponse, SearchListResponse, SharedFolder, SynologyError, SynologyErrorStatus, SynologyResult, SynologyStatusCode, TaskResponse}, progress_reporter::SharedProgressReporter, retry::{non_idempotent_retry_action, retry_action, RetryAction}, totp::generate_totp, ProgressReporter};
+
+const IDLE_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+const SEGMENT_RECORD_INTERVAL: u64 = 8 * 1024 * 1024;
+const TCP_KEEPALIVE: Duration = Duration::from_secs(60);
+const USER_AGENT: &str = concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION"));
 
 #[derive(Clone, Debug)]
 pub struct SynologyFileStation {
-    sid: Option<String>,
-    url: String
+    api_info: HashMap<String, ApiInfo>,
+    client: Client,
+    credential: Option<Credential>,
+    options: ClientOptions,
+    // Shared with clones so that a session renewed by one is used by all of them.
+    sid: Arc<RwLock<Option<String>>>,
+    url: String,
+    user: Option<String>
 }
 
 impl SynologyFileStation {
     #[tracing::instrument]
-    pub fn new(url: &str) -> SynologyFileStation {
-        SynologyFileStation {
-            sid: None,
-            url: url.to_string()
+    pub fn new(url: &str, options: ClientOptions) -> Result<SynologyFileStation, SynologyErrorStatus> {
+        let client = SynologyFileStation::build_client(&options)?;
+
+        Ok(
+            SynologyFileStation {
+                api_info: HashMap::new(),
+                client,
+                credential: None,
+                options,
+                sid: Arc::new(RwLock::new(None)),
+                url: url.to_string(),
+                user: None
+            }
+        )
+    }
+
+    #[tracing::instrument]
+    fn build_client(options: &ClientOptions) -> Result<Client, SynologyErrorStatus> {
+        // Timeouts for whole requests are set per request, as uploads and downloads use the idle timeout instead.
+        let mut builder = Client::builder()
+            .user_agent(USER_AGENT)
+            .connect_timeout(options.connect_timeout)
+            .tcp_keepalive(TCP_KEEPALIVE)
+            .danger_accept_invalid_certs(!options.verify_certificate);
+
+        if let Some(proxy) = &options.proxy {
+            info!("Using proxy \"{}\".", proxy);
+            builder = builder.proxy(Proxy::all(proxy)?);
+        }
+
+        if let Some(certificate_fingerprint) = &options.certificate_fingerprint {
+            // The pin replaces the usual chain and host name checks, which DSM's self-signed certificates fail.
+            info!("Pinning the server certificate to \"{}\".", certificate_fingerprint);
+            builder = builder.use_preconfigured_tls(pinned_tls_config(certificate_fingerprint)?);
+        }
+        else if let Some(ca_bundle) = &options.ca_bundle {
+            info!("Trusting the CA certificates in \"{}\".", ca_bundle.to_string_lossy());
+
+            for certificate in Certificate::from_pem_bundle(&std::fs::read(ca_bundle)?)? {
+                builder = builder.add_root_certificate(certificate);
+            }
+        }
+
+        if !options.verify_certificate {
+            warn!("Certificate verification is disabled.");
         }
+
+        Ok(builder.build()?)
     }
 
+    /// Returns the fingerprint of the server's certificate if it is not trusted, or `None` if it is.
     #[tracing::instrument]
-    async fn get<T: DeserializeOwned>(&self, api: &str, method: &str, version: u32, parameters: &HashMap<&str, String>) -> Result<T, SynologyErrorStatus> {
-        match &self.sid {
+    pub async fn probe_certificate(&self) -> Result<Option<String>, SynologyErrorStatus> {
+        // Only SYNO.API.Info is queried so that nothing sensitive is sent to an untrusted server.
+        let url = format!(
+            "{}/webapi/query.cgi?api=SYNO.API.Info&version=1&method=query&query=SYNO.API.Info",
+            self.url
+        );
+        info!("Get: \"{}\".", url);
+
+        match self.client.get(&url).timeout(self.options.request_timeout).send().await {
+            Ok(_) => Ok(None),
+            Err(error) if error.is_connect() => {
+                info!("Could not connect with certificate verification, checking the certificate.");
+
+                let client = Client::builder()
+                    .user_agent(USER_AGENT)
+                    .connect_timeout(self.options.connect_timeout)
+                    .danger_accept_invalid_certs(true)
+                    .tls_info(true)
+                    .build()?;
+                let response = client.get(&url).timeout(self.options.request_timeout).send().await?;
+
+                match response.extensions().get::<TlsInfo>().and_then(peer_fingerprint) {
+                    Some(fingerprint) => Ok(Some(fingerprint)),
+                    None => Err(SynologyErrorStatus::ReqwestError(error))
+                }
+            },
+            Err(error) => Err(SynologyErrorStatus::ReqwestError(error))
+        }
+    }
+
+    #[tracing::instrument]
+    pub fn user(&self) -> Option<&str> {
+        self.user.as_deref()
+    }
+
+    /// The current session ID, for reusing the session from another process.
+    #[tracing::instrument]
+    pub fn session_id(&self) -> Option<String> {
+        self.sid()
+    }
+
+    #[tracing::instrument]
+    fn sid(&self) -> Option<String> {
+        match self.sid.read() {
+            Ok(sid) => sid.clone(),
+            Err(_) => None
+        }
+    }
+
+    #[tracing::instrument]
+    fn set_sid(&self, sid: Option<String>) {
+        if let Ok(mut current_sid) = self.sid.write() {
+            *current_sid = sid;
+        }
+    }
+
+    #[tracing::instrument(skip(operation))]
+    async fn with_retry<T, TFuture: Future<Output = Result<T, SynologyErrorStatus>>>(&self, operation: impl Fn() -> TFuture) -> Result<T, SynologyErrorStatus> {
+        self.with_retry_action(operation, retry_action).await
+    }
+
+    #[tracing::instrument(skip(operation, retry_action))]
+    async fn with_retry_action<T, TFuture: Future<Output = Result<T, SynologyErrorStatus>>>(
+        &self,
+        operation: impl Fn() -> TFuture,
+        retry_action: fn(&SynologyErrorStatus) -> RetryAction) -> Result<T, SynologyErrorStatus> {
+        let started = Instant::now();
+        let mut attempt = 1;
+        let mut relogin = false;
+
+        loop {
+            let result = if relogin {
+                match Box::pin(self.relogin()).await {
+                    Ok(_) => operation().await,
+                    Err(error) => Err(error)
+                }
+            }
+            else {
+                operation().await
+            };
+
+            let error = match result {
+                Ok(result) => return Ok(result),
+                Err(error) => error
+            };
+
+            let action = retry_action(&error);
+            let backoff = match action {
+                RetryAction::Relogin => Duration::ZERO,
+                _ => self.options.retry.backoff(attempt)
+            };
+
+            if action == RetryAction::Fail ||
+                attempt >= self.options.retry.max_attempts ||
+                started.elapsed() + backoff > self.options.retry.deadline {
+                    return Err(error);
+            }
+
+            warn!("Attempt {} failed, retrying in {} ms: \"{error}\".", attempt, backoff.as_millis());
+            sleep(backoff).await;
+
+            relogin = action == RetryAction::Relogin;
+            attempt += 1;
+        }
+    }
+
+    #[tracing::instrument]
+    async fn relogin(&self) -> Result<(), SynologyErrorStatus> {
+        match &self.credential {
+            Some(credential) => {
+                info!("Session was lost, logging in again.");
+
+                let (sid, _) = self.authenticate(credential, false, None).await?;
+                self.set_sid(Some(sid));
+
+                Ok(())
+            },
+            None => Err(SynologyErrorStatus::NotLoggedIn)
+        }
+    }
+
+    #[tracing::instrument]
+    async fn query_api_info(&mut self) -> Result<(), SynologyErrorStatus> {
+        // SYNO.API.Info is always available from query.cgi, even on older versions of DSM.
+        let url = format!(
+            "{}/webapi/query.cgi?api=SYNO.API.Info&version=1&method=query&query=all",
+            self.url
+        );
+        info!("Get: \"{}\".", url);
+
+        self.api_info = self.with_retry(|| async {
+            let response = self.client.get(&url).timeout(self.options.request_timeout).send().await;
+            self.parse::<HashMap<String, ApiInfo>>(response).await
+        }).await?;
+        info!("Found {} APIs on the server.", self.api_info.len());
+
+        Ok(())
+    }
+
+    #[tracing::instrument]
+    fn resolve_api(&self, api: &str, version: u32) -> Result<(String, u32), SynologyErrorStatus> {
+        // Use the highest version supported by both the server and this client.
+        match self.api_info.get(api) {
+            Some(api_info) => {
+                let version = version.min(api_info.max_version);
+
+                if version < api_info.min_version {
+                    warn!("The server does not support version {} of \"{}\".", version, api);
+
+                    return Err(SynologyErrorStatus::UnsupportedApi(api.to_string()));
+                }
+
+                Ok((format!("{}/webapi/{}", self.url, api_info.path), version))
+            },
+            None => {
+                warn!("The server does not provide \"{}\".", api);
+
+                Err(SynologyErrorStatus::UnsupportedApi(api.to_string()))
+            }
+        }
+    }
+
+    #[tracing::instrument]
+    fn build_url(&self, api: &str, method: &str, version: u32, parameters: &HashMap<&str, String>) -> Result<String, SynologyErrorStatus> {
+        match self.sid() {
             Some(sid) => {
                 info!("Found sid, continuing.");
+                let (api_url, version) = self.resolve_api(api, version)?;
                 let mut url = format!(
-                    "{}/webapi/entry.cgi?api={}&version={}&method={}&_sid={}",
-                    self.url,
+                    "{}?api={}&version={}&method={}&_sid={}",
+                    api_url,
                     api,
                     version,
                     method,
@@ -49,10 +273,7 @@ impl SynologyFileStation {
                     );
                 }
 
-                info!("Get: \"{}\".", url);
-
-                let response = reqwest::get(url).await;
-                Ok(self.parse(response).await?)
+                Ok(url)
             },
             None => {
                 info!("No sid found. Not logged in");
@@ -62,6 +283,85 @@ impl SynologyFileStation {
         }
     }
 
+    #[tracing::instrument]
+    async fn get<T: DeserializeOwned>(&self, api: &str, method: &str, version: u32, parameters: &HashMap<&str, String>) -> Result<T, SynologyErrorStatus> {
+        self.with_retry(|| async {
+            // The url is built for each attempt, as the sid changes when logging in again.
+            let url = self.build_url(api, method, version, parameters)?;
+            info!("Get: \"{}\".", url);
+
+            let response = self.client.get(url).timeout(self.options.request_timeout).send().await;
+            self.parse(response).await
+        }).await
+    }
+
+    /// Like `get`, but for requests that must not be carried out twice.
+    #[tracing::instrument]
+    async fn get_non_idempotent<T: DeserializeOwned>(&self, api: &str, method: &str, version: u32, parameters: &HashMap<&str, String>) -> Result<T, SynologyErrorStatus> {
+        self.with_retry_action(|| async {
+            let url = self.build_url(api, method, version, parameters)?;
+            info!("Get: \"{}\".", url);
+
+            let response = self.client.get(url).timeout(self.options.request_timeout).send().await;
+            self.parse(response).await
+        }, non_idempotent_retry_action).await
+    }
+
+    #[tracing::instrument]
+    async fn get_empty(&self, api: &str, method: &str, version: u32, parameters: &HashMap<&str, String>) -> Result<(), SynologyErrorStatus> {
+        self.with_retry(|| async {
+            let url = self.build_url(api, method, version, parameters)?;
+            info!("Get: \"{}\".", url);
+
+            let response = self.client.get(url).timeout(self.options.request_timeout).send().await;
+            self.parse_empty(response).await
+        }).await
+    }
+
+    #[tracing::instrument]
+    async fn wait_for_idle(&self, last_progress: &Arc<Mutex<Instant>>, body_sent: &AtomicBool) {
+        loop {
+            sleep(IDLE_CHECK_INTERVAL).await;
+
+            let idle_time = match last_progress.lock() {
+                Ok(last_progress) => last_progress.elapsed(),
+                Err(_) => Duration::ZERO
+            };
+
+            // DSM only responds once it has stored the file, which can take a while for large files.
+            if body_sent.load(Ordering::SeqCst) {
+                if idle_time > self.options.response_timeout {
+                    warn!("Upload got no response for {} seconds.", self.options.response_timeout.as_secs());
+
+                    return;
+                }
+            }
+            else if idle_time > self.options.idle_timeout {
+                warn!("Upload made no progress for {} seconds.", self.options.idle_timeout.as_secs());
+
+                return;
+            }
+        }
+    }
+
+    #[tracing::instrument(skip(is_finished))]
+    async fn wait_for_task<T: DeserializeOwned, F: Fn(&T) -> bool>(&self, api: &str, version: u32, taskid: &str, poll_interval: Duration, is_finished: F) -> Result<T, SynologyErrorStatus> {
+        let mut parameters = HashMap::<&str, String>::new();
+        parameters.insert("taskid", taskid.to_string());
+
+        loop {
+            let status: T = self.get(api, "status", version, &parameters).await?;
+
+            if is_finished(&status) {
+                info!("Task \"{}\" finished.", taskid);
+
+                return Ok(status);
+            }
+
+            sleep(poll_interval).await;
+        }
+    }
+
     #[tracing::instrument]
     async fn parse_data_and_error<TData: DeserializeOwned, TError: DeserializeOwned>(&self, response: Result<Response, Error>) -> Result<(Option<TData>, Option<SynologyError<TError>>), SynologyErrorStatus> {
         match response {
@@ -95,15 +395,14 @@ impl SynologyFileStation {
             Err(error) =>
                 match error.status() {
                     Some(status) => Err(SynologyErrorStatus::HttpError(status)),
-                    None => Err(SynologyErrorStatus::UnknownError)
+                    // Keep transport errors so that dropped connections and timeouts can be retried.
+                    None => Err(SynologyErrorStatus::ReqwestError(error))
                 }
         }
     }
 
     #[tracing::instrument]
-    async fn parse<T: DeserializeOwned>(&self, response: Result<Response, Error>) -> Result<T, SynologyErrorStatus> {
-        let (data, error) = self.parse_data_and_error::<T, Vec<HashMap<String, String>>>(response).await?;
-
+    fn parse_error(&self, error: Option<SynologyError<Value>>) -> Result<(), SynologyErrorStatus> {
         match error {
             Some(error) => {
                 warn!("A server error occurred, {}.", error.code);
@@ -114,7 +413,13 @@ impl SynologyFileStation {
                 }
             },
             None => Ok(())
-        }?;
+        }
+    }
+
+    #[tracing::instrument]
+    async fn parse<T: DeserializeOwned>(&self, response: Result<Response, Error>) -> Result<T, SynologyErrorStatus> {
+        let (data, error) = self.parse_data_and_error::<T, Value>(response).await?;
+        self.parse_error(error)?;
 
         match data {
             Some(data) => Ok(data),
@@ -126,6 +431,76 @@ impl SynologyFileStation {
         }
     }
 
+    #[tracing::instrument]
+    async fn parse_empty(&self, response: Result<Response, Error>) -> Result<(), SynologyErrorStatus> {
+        // Some methods only return "success" without any data.
+        let (_, error) = self.parse_data_and_error::<Value, Value>(response).await?;
+
+        self.parse_error(error)
+    }
+
+    #[tracing::instrument]
+    pub async fn check_write_permission(&self, folder_path: &str, file_name: &str, overwrite: bool, create_only: bool) -> Result<(), SynologyErrorStatus> {
+        let mut parameters = HashMap::<&str, String>::new();
+        parameters.insert("path", folder_path.to_string());
+        parameters.insert("filename", file_name.to_string());
+        parameters.insert("overwrite", overwrite.to_string());
+        parameters.insert("create_only", create_only.to_string());
+
+        self.get_empty("SYNO.FileStation.CheckPermission", "write", 3, &parameters).await
+    }
+
+    #[tracing::instrument]
+    pub async fn copy_move_start(
+        &self,
+        paths: &[&str],
+        dest_folder_path: &str,
+        conflict_policy: ConflictPolicy,
+        remove_src: bool,
+        accurate_progress: bool) -> Result<TaskResponse, SynologyErrorStatus> {
+        let mut parameters = HashMap::<&str, String>::new();
+        parameters.insert("path", serde_json::to_string(paths)?);
+        parameters.insert("dest_folder_path", dest_folder_path.to_string());
+        parameters.insert("remove_src", remove_src.to_string());
+        parameters.insert("accurate_progress", accurate_progress.to_string());
+
+        if let Some(overwrite) = conflict_policy.overwrite_parameter() {
+            parameters.insert("overwrite", overwrite);
+        }
+
+        self.get_non_idempotent("SYNO.FileStation.CopyMove", "start", 3, &parameters).await
+    }
+
+    #[tracing::instrument]
+    pub async fn copy_move_status(&self, taskid: &str) -> Result<CopyMoveStatusResponse, SynologyErrorStatus> {
+        let mut parameters = HashMap::<&str, String>::new();
+        parameters.insert("taskid", taskid.to_string());
+
+        self.get("SYNO.FileStation.CopyMove", "status", 3, &parameters).await
+    }
+
+    #[tracing::instrument]
+    pub async fn copy_move_stop(&self, taskid: &str) -> Result<(), SynologyErrorStatus> {
+        let mut parameters = HashMap::<&str, String>::new();
+        parameters.insert("taskid", taskid.to_string());
+
+        self.get_empty("SYNO.FileStation.CopyMove", "stop", 3, &parameters).await
+    }
+
+    #[tracing::instrument]
+    pub async fn copy_move(
+        &self,
+        paths: &[&str],
+        dest_folder_path: &str,
+        conflict_policy: ConflictPolicy,
+        remove_src: bool,
+        poll_interval: Duration) -> Result<CopyMoveStatusResponse, SynologyErrorStatus> {
+        let task = self.copy_move_start(paths, dest_folder_path, conflict_policy, remove_src, false).await?;
+        info!("Started copy/move task \"{}\".", task.taskid);
+
+        self.wait_for_task("SYNO.FileStation.CopyMove", 3, &task.taskid, poll_interval, |status: &CopyMoveStatusResponse| status.finished).await
+    }
+
     #[tracing::instrument]
     pub async fn create_folder(&self, folder_path: &str, name: &str, force_parent: bool) -> Result<CreateFolderResponse, SynologyErrorStatus> {
         let force_parent_string = force_parent.to_string();
@@ -135,75 +510,389 @@ impl SynologyFileStation {
         parameters.insert("name", name.to_string());
         parameters.insert("force_parent", force_parent_string);
 
-        self.get("SYNO.FileStation.CreateFolder", "create", 2, &parameters).await
+        self.get("SYNO.FileStation.CreateFolder", "create", 2, &parameters).await
+    }
+
+    #[tracing::instrument]
+    pub async fn delete(&self, paths: &[&str], recursive: bool) -> Result<(), SynologyErrorStatus> {
+        let mut parameters = HashMap::<&str, String>::new();
+        parameters.insert("path", serde_json::to_string(paths)?);
+        parameters.insert("recursive", recursive.to_string());
+
+        self.get_empty("SYNO.FileStation.Delete", "delete", 2, &parameters).await
+    }
+
+    #[tracing::instrument]
+    pub async fn delete_start(&self, paths: &[&str], accurate_progress: bool, recursive: bool) -> Result<TaskResponse, SynologyErrorStatus> {
+        let mut parameters = HashMap::<&str, String>::new();
+        parameters.insert("path", serde_json::to_string(paths)?);
+        parameters.insert("accurate_progress", accurate_progress.to_string());
+        parameters.insert("recursive", recursive.to_string());
+
+        self.get_non_idempotent("SYNO.FileStation.Delete", "start", 2, &parameters).await
+    }
+
+    #[tracing::instrument]
+    pub async fn delete_status(&self, taskid: &str) -> Result<DeleteStatusResponse, SynologyErrorStatus> {
+        let mut parameters = HashMap::<&str, String>::new();
+        parameters.insert("taskid", taskid.to_string());
+
+        self.get("SYNO.FileStation.Delete", "status", 2, &parameters).await
+    }
+
+    #[tracing::instrument]
+    pub async fn delete_stop(&self, taskid: &str) -> Result<(), SynologyErrorStatus> {
+        let mut parameters = HashMap::<&str, String>::new();
+        parameters.insert("taskid", taskid.to_string());
+
+        self.get_empty("SYNO.FileStation.Delete", "stop", 2, &parameters).await
+    }
+
+    #[tracing::instrument]
+    pub async fn delete_and_wait(&self, paths: &[&str], recursive: bool, poll_interval: Duration) -> Result<DeleteStatusResponse, SynologyErrorStatus> {
+        let task = self.delete_start(paths, false, recursive).await?;
+        info!("Started delete task \"{}\".", task.taskid);
+
+        self.wait_for_task("SYNO.FileStation.Delete", 2, &task.taskid, poll_interval, |status: &DeleteStatusResponse| status.finished).await
+    }
+
+    #[tracing::instrument]
+    pub async fn dir_size_start(&self, paths: &[&str]) -> Result<TaskResponse, SynologyErrorStatus> {
+        let mut parameters = HashMap::<&str, String>::new();
+        parameters.insert("path", serde_json::to_string(paths)?);
+
+        self.get("SYNO.FileStation.DirSize", "start", 2, &parameters).await
+    }
+
+    #[tracing::instrument]
+    pub async fn dir_size_status(&self, taskid: &str) -> Result<DirSizeStatusResponse, SynologyErrorStatus> {
+        let mut parameters = HashMap::<&str, String>::new();
+        parameters.insert("taskid", taskid.to_string());
+
+        self.get("SYNO.FileStation.DirSize", "status", 2, &parameters).await
+    }
+
+    #[tracing::instrument]
+    pub async fn dir_size_stop(&self, taskid: &str) -> Result<(), SynologyErrorStatus> {
+        let mut parameters = HashMap::<&str, String>::new();
+        parameters.insert("taskid", taskid.to_string());
+
+        self.get_empty("SYNO.FileStation.DirSize", "stop", 2, &parameters).await
+    }
+
+    #[tracing::instrument]
+    pub async fn dir_size(&self, paths: &[&str], poll_interval: Duration) -> Result<DirSizeStatusResponse, SynologyErrorStatus> {
+        let task = self.dir_size_start(paths).await?;
+        info!("Started directory size task \"{}\".", task.taskid);
+
+        self.wait_for_task("SYNO.FileStation.DirSize", 2, &task.taskid, poll_interval, |status: &DirSizeStatusResponse| status.finished).await
+    }
+
+    #[tracing::instrument]
+    pub async fn download<TProgressReporter: ProgressReporter + 'static>(
+        &self,
+        source_file_path: &str,
+        target_directory_path: &Path,
+        progress_reporter: Option<TProgressReporter>) -> Result<PathBuf, SynologyErrorStatus> {
+            let file_name = match source_file_path.split("/").last() {
+                Some(file_name) => Ok(file_name),
+                None => Err(SynologyErrorStatus::UnknownError)
+            }?;
+            info!("Found file name: \"{}\".", file_name);
+
+            let mut target_file_path = PathBuf::new();
+            target_file_path.push(target_directory_path);
+            target_file_path.push(file_name);
+
+            info!("Target File Path: \"{}\".", target_file_path.as_os_str().to_string_lossy());
+
+            let mut state = self.get_download_state(source_file_path).await?;
+
+            let partial_download = PartialDownload::new(&target_file_path);
+            let progress_reporter = SharedProgressReporter::new(progress_reporter);
+
+            // An interrupted single connection download is finished the same way rather than started over.
+            let resumable_stream = partial_download.has_resumable_stream(&state).await;
+            if resumable_stream {
+                info!("Resuming the interrupted download over a single connection.");
+            }
+
+            if !resumable_stream && self.options.download_connections > 1 && state.size >= self.options.parallel_download_threshold {
+                match self.download_parallel(source_file_path, &partial_download, &state, &progress_reporter).await {
+                    Ok(_) => {
+                        partial_download.finish(&target_file_path).await?;
+
+                        return Ok(target_file_path);
+                    },
+                    Err(SynologyErrorStatus::HttpError(StatusCode::OK)) => {
+                        warn!("Server does not support ranges, falling back to a single connection.");
+                        partial_download.discard().await?;
+                    },
+                    Err(SynologyErrorStatus::ObjectChanged) => {
+                        warn!("Object changed during the download, downloading it again over a single connection.");
+                        partial_download.discard().await?;
+                        state = self.get_download_state(source_file_path).await?;
+                    },
+                    // The progress of each segment is kept so that the next attempt continues from it.
+                    Err(error) => return Err(error)
+                }
+            }
+
+            // Each attempt resumes from what the previous attempts left in the staging file.
+            self.with_retry(|| self.download_to_staging(source_file_path, &partial_download, &state, &progress_reporter)).await?;
+            partial_download.finish(&target_file_path).await?;
+
+            Ok(target_file_path)
+    }
+
+    #[tracing::instrument]
+    async fn download_parallel<TProgressReporter: ProgressReporter + 'static>(
+        &self,
+        source_file_path: &str,
+        partial_download: &PartialDownload,
+        state: &DownloadState,
+        progress_reporter: &SharedProgressReporter<TProgressReporter>) -> Result<(), SynologyErrorStatus> {
+            let connections = self.options.download_connections as u64;
+            let segment_size = state.size.div_ceil(connections);
+            let ranges = (0..connections)
+                .map(|index| index * segment_size)
+                .filter(|start| *start < state.size)
+                .map(|start| (start, (start + segment_size).min(state.size) - 1))
+                .collect::<Vec<(u64, u64)>>();
+            info!("Downloading {} bytes over {} connections.", state.size, ranges.len());
+
+            let segments = partial_download.resume_segments(state, &ranges).await?;
+
+            let resumed: u64 = segments.iter().map(|segment| segment.downloaded).sum();
+            if resumed > 0 {
+                info!("{} bytes were downloaded by an earlier attempt.", resumed);
+                progress_reporter.add(resumed as usize);
+            }
+
+            let downloads = segments
+                .into_iter()
+                .enumerate()
+                .filter(|(_, segment)| !segment.is_complete())
+                .map(|(index, segment)| self.download_segment(source_file_path, partial_download, state, index, segment, progress_reporter));
+
+            try_join_all(downloads).await?;
+
+            // Segments downloaded before the object changed would be mixed with the new object.
+            self.check_download_state(source_file_path, state).await
+    }
+
+    /// The size and modified time identify the remote object so that we only resume the same object.
+    #[tracing::instrument]
+    async fn get_download_state(&self, source_file_path: &str) -> Result<DownloadState, SynologyErrorStatus> {
+        let info = self.get_info(&[source_file_path], AdditionalInfo::new().size().time()).await?;
+        let additional = match info.files.into_iter().find(|file| file.exists()) {
+            Some(file) => Ok(file.additional),
+            None => Err(SynologyErrorStatus::ServerError(SynologyStatusCode::NoSuchFileOrDirectory))
+        }?;
+
+        Ok(
+            DownloadState {
+                size: additional.as_ref().and_then(|additional| additional.size).unwrap_or_default(),
+                mtime: additional.as_ref().and_then(|additional| additional.time.as_ref()).map(|time| time.mtime).unwrap_or_default()
+            }
+        )
+    }
+
+    #[tracing::instrument]
+    async fn check_download_state(&self, source_file_path: &str, state: &DownloadState) -> Result<(), SynologyErrorStatus> {
+        if self.get_download_state(source_file_path).await? != *state {
+            warn!("\"{}\" changed on the server.", source_file_path);
+
+            return Err(SynologyErrorStatus::ObjectChanged);
+        }
+
+        Ok(())
+    }
+
+    /// Downloads a segment, retrying from where the previous attempt stopped.
+    #[tracing::instrument(skip(progress_reporter))]
+    async fn download_segment<TProgressReporter: ProgressReporter + 'static>(
+        &self,
+        source_file_path: &str,
+        partial_download: &PartialDownload,
+        state: &DownloadState,
+        index: usize,
+        segment: SegmentState,
+        progress_reporter: &SharedProgressReporter<TProgressReporter>) -> Result<(), SynologyErrorStatus> {
+            let downloaded = AtomicU64::new(segment.downloaded);
+
+            self.with_retry(|| async {
+                // Ranges carry no validator DSM is known to honor, so make sure each attempt continues the same object.
+                self.check_download_state(source_file_path, state).await?;
+
+                self.download_segment_once(source_file_path, partial_download, index, &segment, &downloaded, progress_reporter).await
+            }).await
+    }
+
+    #[tracing::instrument(skip(progress_reporter))]
+    async fn download_segment_once<TProgressReporter: ProgressReporter + 'static>(
+        &self,
+        source_file_path: &str,
+        partial_download: &PartialDownload,
+        index: usize,
+        segment: &SegmentState,
+        downloaded: &AtomicU64,
+        progress_reporter: &SharedProgressReporter<TProgressReporter>) -> Result<(), SynologyErrorStatus> {
+            let mut parameters = HashMap::<&str, String>::new();
+            parameters.insert("path", source_file_path.to_string());
+            parameters.insert("mode", "download".to_string());
+
+            let url = self.build_url("SYNO.FileStation.Download", "download", 2, &parameters)?;
+            info!("Get: \"{}\".", url);
+
+            let offset = segment.start + downloaded.load(Ordering::SeqCst);
+            let mut response = self.client.get(url)
+                .header(RANGE, format!("bytes={}-{}", offset, segment.end))
+                .send()
+                .await?;
+
+            if response.status() != StatusCode::PARTIAL_CONTENT {
+                return Err(SynologyErrorStatus::HttpError(response.status()));
+            }
+
+            let mut target_stream = partial_download.open_at(offset).await?;
+
+            let mut unrecorded = 0;
+            let result: Result<(), SynologyErrorStatus> = async {
+                while let Some(chunk) = self.next_chunk(&mut response).await? {
+                    target_stream.write_all(&chunk).await?;
+                    downloaded.fetch_add(chunk.len() as u64, Ordering::SeqCst);
+                    unrecorded += chunk.len() as u64;
+
+                    progress_reporter.add(chunk.len());
+
+                    if unrecorded >= SEGMENT_RECORD_INTERVAL {
+                        target_stream.flush().await?;
+                        partial_download.record_segment(index, downloaded.load(Ordering::SeqCst)).await?;
+                        unrecorded = 0;
+                    }
+                }
+
+                Ok(())
+            }.await;
+
+            // Record what was written even if the connection dropped, so that nothing is downloaded twice.
+            target_stream.flush().await?;
+            partial_download.record_segment(index, downloaded.load(Ordering::SeqCst)).await?;
+            result?;
+
+            if segment.start + downloaded.load(Ordering::SeqCst) != segment.end + 1 {
+                return Err(SynologyErrorStatus::IoError(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    "The download ended before the whole segment was received.")));
+            }
+
+            Ok(())
+    }
+
+    #[tracing::instrument]
+    async fn next_chunk(&self, response: &mut Response) -> Result<Option<Bytes>, SynologyErrorStatus> {
+        match timeout(self.options.idle_timeout, response.chunk()).await {
+            Ok(chunk) => Ok(chunk?),
+            Err(_) => {
+                warn!("Download made no progress for {} seconds.", self.options.idle_timeout.as_secs());
+
+                Err(SynologyErrorStatus::Timeout)
+            }
+        }
+    }
+
+    #[tracing::instrument]
+    async fn download_to_staging<TProgressReporter: ProgressReporter + 'static>(
+        &self,
+        source_file_path: &str,
+        partial_download: &PartialDownload,
+        state: &DownloadState,
+        progress_reporter: &SharedProgressReporter<TProgressReporter>) -> Result<(), SynologyErrorStatus> {
+            let mut offset = partial_download.resume_offset(state).await?;
+
+            if offset > 0 && offset == state.size {
+                info!("Staging file is already complete.");
+
+                return Ok(());
+            }
+
+            let mut parameters = HashMap::<&str, String>::new();
+            parameters.insert("path", source_file_path.to_string());
+            parameters.insert("mode", "download".to_string());
+
+            let url = self.build_url("SYNO.FileStation.Download", "download", 2, &parameters)?;
+            info!("Get: \"{}\".", url);
+
+            let mut request = self.client.get(url);
+
+            if offset > 0 {
+                request = request.header(RANGE, format!("bytes={}-", offset));
+            }
+
+            let mut response = request.send().await?;
+
+            let append = match response.status() {
+                StatusCode::PARTIAL_CONTENT if offset > 0 => Ok(true),
+                StatusCode::OK => {
+                    if offset > 0 {
+                        info!("Server does not support resuming, downloading the whole file.");
+                        partial_download.restart(state).await?;
+                        offset = 0;
+                    }
+
+                    Ok(false)
+                },
+                status => Err(SynologyErrorStatus::HttpError(status))
+            }?;
+
+            let mut target_stream = partial_download.open(append).await?;
+
+            // Bytes reported by an earlier attempt are not reported again.
+            progress_reporter.set_total(offset as usize);
+
+            let mut bytes_downloaded = offset;
+            while let Some(chunk) = self.next_chunk(&mut response).await? {
+                target_stream.write_all(&chunk).await?;
+                bytes_downloaded += chunk.len() as u64;
+
+                progress_reporter.set_total(bytes_downloaded as usize);
+            }
+
+            target_stream.flush().await?;
+
+            if state.size > 0 && bytes_downloaded != state.size {
+                return Err(SynologyErrorStatus::IoError(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    "The download ended before the whole file was received.")));
+            }
+
+            Ok(())
+    }
+
+    #[tracing::instrument]
+    fn get_next_offset(&self, offset: u64, page_len: usize, total: u64) -> Option<u64> {
+        let next_offset = offset + page_len as u64;
+
+        // Stop on an empty page as well so that a changing folder can't loop forever.
+        if page_len == 0 || next_offset >= total {
+            None
+        }
+        else {
+            Some(next_offset)
+        }
     }
 
     #[tracing::instrument]
-    pub async fn download<TProgressReporter: ProgressReporter + 'static>(
-        &self,
-        source_file_path: &str,
-        target_directory_path: &Path,
-        mut progress_reporter: Option<TProgressReporter>) -> Result<PathBuf, SynologyErrorStatus> {
-            match &self.sid {
-                Some(sid) => {
-                    info!("Found sid, continuing.");
-                    match source_file_path.split("/").last() {
-                        Some(file_name) => {
-                            info!("Found file name: \"{}\".", file_name);
-                            let url = format!(
-                                "{}/webapi/entry.cgi?api={}&version={}&method={}&_sid={}&path={}&mode=download",
-                                self.url,
-                                "SYNO.FileStation.Download",
-                                2,
-                                "download",
-                                sid.as_str(),
-                                source_file_path
-                            );
-                            info!("Get: \"{}\".", url);
-        
-                            let mut target_file_path = PathBuf::new();
-                            target_file_path.push(target_directory_path);
-                            target_file_path.push(file_name);
-
-                            info!("Target File Path: \"{}\".", target_file_path.as_os_str().to_string_lossy());
-        
-                            let mut target_stream = File::create(&target_file_path).await?;
-                            let mut response = reqwest::get(url).await?;
-
-                            while let Ok(chunk) = response.chunk().await {
-                                if let Some(chunk) = chunk {
-                                    let write_len = target_stream.write(&chunk).await?;
-
-                                    if let Some(progress_reporter) = &mut progress_reporter {
-                                        let result = progress_reporter.update(write_len);
-
-                                        if let Err(error) = result {
-                                            warn!("An error occurred reporting progress: \"{error}\".");
-                                        }
-                                    }
-                                }
-                                else {
-                                    break;
-                                }
-                            }
+    pub async fn get_info(&self, paths: &[&str], additional: AdditionalInfo) -> Result<GetInfoResponse, SynologyErrorStatus> {
+        let mut parameters = HashMap::<&str, String>::new();
+        parameters.insert("path", serde_json::to_string(paths)?);
 
-                            if let Some(mut progress_reporter) = progress_reporter {
-                                if let Some(total_bytes) = response.content_length(){
-                                    info!("Reporting complete progress.");
-                                    let result = progress_reporter.update(total_bytes as usize );
-                
-                                    if let Err(error) = result {
-                                        warn!("An error occurred reporting progress: \"{error}\".");
-                                    }
-                                }
-                            }
-                            Ok(target_file_path)
-                        },
-                        None => Err(SynologyErrorStatus::UnknownError)
-                    }
-                }
-                None => Err(SynologyErrorStatus::NotLoggedIn)
-            }
+        if let Some(additional) = additional.to_parameter() {
+            parameters.insert("additional", additional);
+        }
+
+        self.get("SYNO.FileStation.List", "getinfo", 2, &parameters).await
     }
 
     #[allow(clippy::too_many_arguments)] // Allow this so that we better match the Synology API.
@@ -218,13 +907,7 @@ impl SynologyFileStation {
         pattern: Option<String>,
         file_type: Option<String>,
         goto_path: Option<String>,
-        include_real_path: bool,
-        include_size: bool,
-        include_owner: bool,
-        include_time: bool,
-        include_perm: bool,
-        include_mount_point_type: bool,
-        include_type: bool
+        additional: AdditionalInfo
     ) -> Result<ListResponse, SynologyErrorStatus> {
         let mut parameters = HashMap::<&str, String>::new();
         parameters.insert("folder_path", folder_path.to_string());
@@ -257,44 +940,13 @@ impl SynologyFileStation {
             parameters.insert("goto_path", goto_path);
         }
 
-        let mut additional: String = String::new();
-
-        if include_real_path {
-            additional = format!("{},real_path", additional);
-        }
-
-        if include_size {
-            additional = format!("{},size", additional);
-        }
-
-        if include_owner {
-            additional = format!("{},owner", additional);
-        }
-
-        if include_time {
-            additional = format!("{},time", additional);
-        }
-
-        if include_perm {
-            additional = format!("{},perm", additional);
-        }
-
-        if include_mount_point_type {
-            additional = format!("{},mount_point_type", additional);
-        }
-
-        if include_type {
-            additional = format!("{},type", additional);
-        }
-
-        if !additional.is_empty() {
-            parameters.insert("additional", additional[1..].to_string());
+        if let Some(additional) = additional.to_parameter() {
+            parameters.insert("additional", additional);
         }
 
         self.get("SYNO.FileStation.List", "list", 2, &parameters).await
     }
 
-    #[allow(clippy::too_many_arguments)] // Allow this so that we better match the Synology API.
     #[tracing::instrument]
     pub async fn list_share(
         &self,
@@ -303,13 +955,7 @@ impl SynologyFileStation {
         sort_by: Option<String>,
         sort_direction: Option<String>,
         only_writable: Option<bool>,
-        include_real_path: bool,
-        include_size: bool,
-        include_owner: bool,
-        include_time: bool,
-        include_perm: bool,
-        include_mount_point_type: bool,
-        include_volume_status: bool
+        additional: AdditionalInfo
     ) -> Result<ListShareResponse, SynologyErrorStatus> {
         let mut parameters = HashMap::<&str, String>::new();
 
@@ -333,55 +979,137 @@ impl SynologyFileStation {
             parameters.insert("only_writable", only_writable.to_string());
         }
 
-        let mut additional: String = String::new();
-
-        if include_real_path {
-            additional = format!("{},real_path", additional);
+        if let Some(additional) = additional.to_parameter() {
+            parameters.insert("additional", additional);
         }
 
-        if include_size {
-            additional = format!("{},size", additional);
-        }
+        self.get("SYNO.FileStation.List", "list_share", 2, &parameters).await
+    }
 
-        if include_owner {
-            additional = format!("{},owner", additional);
-        }
+    #[tracing::instrument]
+    pub fn list_stream<'a>(
+        &'a self,
+        folder_path: &'a str,
+        page_size: u64,
+        pattern: Option<String>,
+        file_type: Option<String>,
+        additional: AdditionalInfo
+    ) -> impl Stream<Item = Result<File, SynologyErrorStatus>> + 'a {
+        stream::try_unfold(Some(0), move |offset| {
+            let pattern = pattern.clone();
+            let file_type = file_type.clone();
+
+            async move {
+                match offset {
+                    Some(offset) => {
+                        info!("Listing \"{}\" from offset {}.", folder_path, offset);
+
+                        let page = self.list(
+                            folder_path, Some(offset), Some(page_size), None, None, pattern, file_type, None,
+                            additional).await?;
+                        let next_offset = self.get_next_offset(offset, page.files.len(), page.total);
+
+                        Ok::<_, SynologyErrorStatus>(Some((stream::iter(page.files.into_iter().map(Ok)), next_offset)))
+                    },
+                    None => Ok(None)
+                }
+            }
+        }).try_flatten()
+    }
 
-        if include_time {
-            additional = format!("{},time", additional);
-        }
+    #[tracing::instrument]
+    pub fn list_share_stream(&self, page_size: u64, additional: AdditionalInfo) -> impl Stream<Item = Result<SharedFolder, SynologyErrorStatus>> + '_ {
+        stream::try_unfold(Some(0), move |offset| async move {
+            match offset {
+                Some(offset) => {
+                    info!("Listing shares from offset {}.", offset);
+
+                    let page = self.list_share(
+                        Some(offset), Some(page_size), None, None, None,
+                        additional).await?;
+                    let next_offset = self.get_next_offset(offset, page.shares.len(), page.total);
+
+                    Ok::<_, SynologyErrorStatus>(Some((stream::iter(page.shares.into_iter().map(Ok)), next_offset)))
+                },
+                None => Ok(None)
+            }
+        }).try_flatten()
+    }
 
-        if include_perm {
-            additional = format!("{},perm", additional);
+    #[tracing::instrument]
+    pub async fn login(&mut self, credential: &Credential, enable_device_token: bool, totp: Option<String>) -> Result<Credential, SynologyErrorStatus> {
+        if self.api_info.is_empty() {
+            info!("Querying the APIs supported by the server.");
+            self.query_api_info().await?;
         }
 
-        if include_mount_point_type {
-            additional = format!("{},mount_point_type", additional);
-        }
+        let (sid, cred) = self.authenticate(credential, enable_device_token, totp).await?;
+        self.set_sid(Some(sid));
+        self.user = Some(credential.user.to_string());
+
+        // Keep the device ID so that logging in again does not need a TOTP.
+        let cred = Credential {
+            device_id: cred.device_id.or(credential.device_id.clone()),
+            ..credential.clone()
+        };
+        self.credential = Some(cred.clone());
 
-        if include_volume_status {
-            additional = format!("{},volume_status", additional);
+        Ok(cred)
+    }
+
+    /// Continues a session started by another process, returning an error if it is no longer valid.
+    #[tracing::instrument]
+    pub async fn resume_session(&mut self, credential: &Credential, sid: &str) -> Result<(), SynologyErrorStatus> {
+        if self.api_info.is_empty() {
+            info!("Querying the APIs supported by the server.");
+            self.query_api_info().await?;
         }
 
-        if !additional.is_empty() {
-            parameters.insert("additional", additional[1..].to_string());
+        self.set_sid(Some(sid.to_string()));
+
+        // The credential is not set yet, so a lost session fails here instead of logging in again.
+        if let Err(error) = self.get::<Value>("SYNO.FileStation.Info", "get", 2, &HashMap::new()).await {
+            info!("The session could not be resumed: \"{error}\".");
+            self.set_sid(None);
+
+            return Err(error);
         }
 
-        self.get("SYNO.FileStation.List", "list_share", 2, &parameters).await
+        self.user = Some(credential.user.to_string());
+        self.credential = Some(credential.clone());
+
+        Ok(())
     }
 
     #[tracing::instrument]
-    pub async fn login(&mut self, credential: &Credential, enable_device_token: bool, totp: Option<String>) -> Result<Credential, SynologyErrorStatus> {
+    async fn authenticate(&self, credential: &Credential, enable_device_token: bool, totp: Option<String>) -> Result<(String, Credential), SynologyErrorStatus> {
+        match self.authenticate_once(credential, enable_device_token, totp.clone()).await {
+            Err(SynologyErrorStatus::NoTotp) if totp.is_none() => match &credential.totp_secret {
+                Some(totp_secret) => {
+                    info!("Generating the TOTP from the stored secret.");
+
+                    let totp = generate_totp(totp_secret, SystemTime::now())?;
+                    self.authenticate_once(credential, enable_device_token, Some(totp)).await
+                },
+                None => Err(SynologyErrorStatus::NoTotp)
+            },
+            result => result
+        }
+    }
+
+    #[tracing::instrument]
+    async fn authenticate_once(&self, credential: &Credential, enable_device_token: bool, totp: Option<String>) -> Result<(String, Credential), SynologyErrorStatus> {
         let device_name = format!(
             "{}::{}",
             hostname::get()?.to_string_lossy(),
             "rust_synology_api"
         );
 
+        let (api_url, version) = self.resolve_api("SYNO.API.Auth", 6)?;
         let mut login_url = format!(
-            "{}/webapi/entry.cgi?api=SYNO.API.Auth&version={}&method=login&account={}&passwd={}&device_name={}&session=FileStation&fromat=sid",
-            self.url,
-            6,
+            "{}?api=SYNO.API.Auth&version={}&method=login&account={}&passwd={}&device_name={}&session=FileStation&fromat=sid",
+            api_url,
+            version,
             encode(&credential.user),
             encode(&credential.password), // Encode the password in case it has characters not allowed in URLs in it.
             encode(&device_name)
@@ -415,8 +1143,10 @@ impl SynologyFileStation {
         }
 
         // Make initial request to the server.  This will fail if the user needs a TOTP.
-        let response = reqwest::get(login_url).await;
-        let (login_result, login_error) = self.parse_data_and_error::<LoginResponse, LoginError>(response).await?;
+        let (login_result, login_error) = self.with_retry(|| async {
+            let response = self.client.get(&login_url).timeout(self.options.request_timeout).send().await;
+            self.parse_data_and_error::<LoginResponse, LoginError>(response).await
+        }).await?;
 
         match login_error {
             Some(login_error) => 
@@ -448,17 +1178,286 @@ impl SynologyFileStation {
 
         match login_result {
             Some(login_result) => {
-                self.sid = Some(login_result.sid);
-
                 let mut cred = Credential::new(credential.user.to_string(), credential.password.to_string());
                 cred.device_id = login_result.did;
 
-                Ok(cred)
+                Ok((login_result.sid, cred))
             },
             None => Err(SynologyErrorStatus::UnknownError)
         }
     }
 
+    #[tracing::instrument]
+    pub async fn logout(&mut self) -> Result<(), SynologyErrorStatus> {
+        let mut parameters = HashMap::<&str, String>::new();
+        parameters.insert("session", "FileStation".to_string());
+
+        // Do not log in again only to log out.
+        self.credential = None;
+
+        let result = self.get_empty("SYNO.API.Auth", "logout", 6, &parameters).await;
+
+        // The session should not be reused, even if the server did not accept the logout.
+        self.set_sid(None);
+        self.user = None;
+
+        result
+    }
+
+    #[tracing::instrument]
+    pub async fn md5_start(&self, file_path: &str) -> Result<TaskResponse, SynologyErrorStatus> {
+        let mut parameters = HashMap::<&str, String>::new();
+        parameters.insert("file_path", file_path.to_string());
+
+        self.get("SYNO.FileStation.MD5", "start", 2, &parameters).await
+    }
+
+    #[tracing::instrument]
+    pub async fn md5_status(&self, taskid: &str) -> Result<Md5StatusResponse, SynologyErrorStatus> {
+        let mut parameters = HashMap::<&str, String>::new();
+        parameters.insert("taskid", taskid.to_string());
+
+        self.get("SYNO.FileStation.MD5", "status", 2, &parameters).await
+    }
+
+    #[tracing::instrument]
+    pub async fn md5_stop(&self, taskid: &str) -> Result<(), SynologyErrorStatus> {
+        let mut parameters = HashMap::<&str, String>::new();
+        parameters.insert("taskid", taskid.to_string());
+
+        self.get_empty("SYNO.FileStation.MD5", "stop", 2, &parameters).await
+    }
+
+    #[tracing::instrument]
+    pub async fn md5(&self, file_path: &str, poll_interval: Duration) -> Result<String, SynologyErrorStatus> {
+        let task = self.md5_start(file_path).await?;
+        info!("Started MD5 task \"{}\".", task.taskid);
+
+        let status = self.wait_for_task("SYNO.FileStation.MD5", 2, &task.taskid, poll_interval, |status: &Md5StatusResponse| status.finished).await?;
+
+        match status.md5 {
+            Some(md5) => Ok(md5),
+            None => {
+                warn!("MD5 task finished without a checksum.");
+
+                Err(SynologyErrorStatus::UnknownError)
+            }
+        }
+    }
+
+    #[tracing::instrument]
+    pub async fn rename(&self, paths: &[&str], names: &[&str]) -> Result<RenameResponse, SynologyErrorStatus> {
+        let mut parameters = HashMap::<&str, String>::new();
+        parameters.insert("path", serde_json::to_string(paths)?);
+        parameters.insert("name", serde_json::to_string(names)?);
+
+        self.get("SYNO.FileStation.Rename", "rename", 2, &parameters).await
+    }
+
+    #[tracing::instrument]
+    pub async fn search_start(&self, criteria: &SearchCriteria) -> Result<TaskResponse, SynologyErrorStatus> {
+        let mut parameters = HashMap::<&str, String>::new();
+        parameters.insert("folder_path", serde_json::to_string(&criteria.folder_paths)?);
+        parameters.insert("recursive", criteria.recursive.to_string());
+
+        if let Some(pattern) = &criteria.pattern {
+            parameters.insert("pattern", pattern.to_string());
+        }
+
+        if let Some(extension) = &criteria.extension {
+            parameters.insert("extension", extension.to_string());
+        }
+
+        if let Some(file_type) = &criteria.file_type {
+            parameters.insert("filetype", file_type.to_string());
+        }
+
+        if let Some(size_from) = criteria.size_from {
+            parameters.insert("size_from", size_from.to_string());
+        }
+
+        if let Some(size_to) = criteria.size_to {
+            parameters.insert("size_to", size_to.to_string());
+        }
+
+        if let Some(mtime_from) = criteria.mtime_from {
+            parameters.insert("mtime_from", mtime_from.to_string());
+        }
+
+        if let Some(mtime_to) = criteria.mtime_to {
+            parameters.insert("mtime_to", mtime_to.to_string());
+        }
+
+        self.get("SYNO.FileStation.Search", "start", 2, &parameters).await
+    }
+
+    #[tracing::instrument]
+    pub async fn search_list(
+        &self,
+        taskid: &str,
+        offset: Option<u64>,
+        limit: Option<u64>,
+        additional: AdditionalInfo
+    ) -> Result<SearchListResponse, SynologyErrorStatus> {
+        let mut parameters = HashMap::<&str, String>::new();
+        parameters.insert("taskid", taskid.to_string());
+
+        if let Some(offset) = offset {
+            parameters.insert("offset", offset.to_string());
+        }
+
+        if let Some(limit) = limit {
+            parameters.insert("limit", limit.to_string());
+        }
+
+        if let Some(additional) = additional.to_parameter() {
+            parameters.insert("additional", additional);
+        }
+
+        self.get("SYNO.FileStation.Search", "list", 2, &parameters).await
+    }
+
+    #[tracing::instrument]
+    pub async fn search_stop(&self, taskid: &str) -> Result<(), SynologyErrorStatus> {
+        let mut parameters = HashMap::<&str, String>::new();
+        parameters.insert("taskid", taskid.to_string());
+
+        self.get_empty("SYNO.FileStation.Search", "stop", 2, &parameters).await
+    }
+
+    #[tracing::instrument]
+    pub async fn search_clean(&self, taskid: &str) -> Result<(), SynologyErrorStatus> {
+        let mut parameters = HashMap::<&str, String>::new();
+        parameters.insert("taskid", taskid.to_string());
+
+        self.get_empty("SYNO.FileStation.Search", "clean", 2, &parameters).await
+    }
+
+    #[tracing::instrument]
+    pub async fn search(
+        &self,
+        criteria: &SearchCriteria,
+        page_size: u64,
+        additional: AdditionalInfo,
+        poll_interval: Duration
+    ) -> Result<Vec<File>, SynologyErrorStatus> {
+        let task = self.search_start(criteria).await?;
+        info!("Started search task \"{}\".", task.taskid);
+
+        let result = self.search_collect(&task.taskid, page_size, additional, poll_interval).await;
+
+        // The server keeps search results until they are cleaned, so always try to clean up.
+        if let Err(error) = self.search_stop(&task.taskid).await {
+            warn!("An error occurred stopping the search task: \"{error}\".");
+        }
+
+        if let Err(error) = self.search_clean(&task.taskid).await {
+            warn!("An error occurred cleaning the search task: \"{error}\".");
+        }
+
+        result
+    }
+
+    #[tracing::instrument]
+    async fn search_collect(
+        &self,
+        taskid: &str,
+        page_size: u64,
+        additional: AdditionalInfo,
+        poll_interval: Duration
+    ) -> Result<Vec<File>, SynologyErrorStatus> {
+        loop {
+            // DSM treats a limit of 0 as no limit, so poll with a single result to keep each poll small.
+            let status = self.search_list(taskid, Some(0), Some(1), AdditionalInfo::new()).await?;
+
+            if status.finished {
+                info!("Search task \"{}\" finished with {} results.", taskid, status.total);
+
+                break;
+            }
+
+            sleep(poll_interval).await;
+        }
+
+        let mut files = Vec::<File>::new();
+        let mut offset = Some(0);
+
+        while let Some(current_offset) = offset {
+            let page = self.search_list(taskid, Some(current_offset), Some(page_size), additional).await?;
+            offset = self.get_next_offset(current_offset, page.files.len(), page.total);
+
+            files.extend(page.files);
+        }
+
+        Ok(files)
+    }
+
+    #[tracing::instrument]
+    pub async fn sharing_create(&self, paths: &[&str], options: &SharingLinkOptions) -> Result<CreateSharingLinkResponse, SynologyErrorStatus> {
+        let mut parameters = HashMap::<&str, String>::new();
+        parameters.insert("path", serde_json::to_string(paths)?);
+
+        if let Some(password) = &options.password {
+            parameters.insert("password", password.to_string());
+        }
+
+        if let Some(date_expired) = &options.date_expired {
+            parameters.insert("date_expired", date_expired.to_string());
+        }
+
+        if let Some(date_available) = &options.date_available {
+            parameters.insert("date_available", date_available.to_string());
+        }
+
+        if let Some(expire_times) = options.expire_times {
+            parameters.insert("expire_times", expire_times.to_string());
+        }
+
+        self.get_non_idempotent("SYNO.FileStation.Sharing", "create", 3, &parameters).await
+    }
+
+    #[tracing::instrument]
+    pub async fn sharing_delete(&self, ids: &[&str]) -> Result<(), SynologyErrorStatus> {
+        let mut parameters = HashMap::<&str, String>::new();
+        parameters.insert("id", serde_json::to_string(ids)?);
+
+        self.get_empty("SYNO.FileStation.Sharing", "delete", 3, &parameters).await
+    }
+
+    #[tracing::instrument]
+    pub async fn sharing_list(
+        &self,
+        offset: Option<u64>,
+        limit: Option<u64>,
+        sort_by: Option<String>,
+        sort_direction: Option<String>,
+        force_clean: Option<bool>
+    ) -> Result<ListSharingLinkResponse, SynologyErrorStatus> {
+        let mut parameters = HashMap::<&str, String>::new();
+
+        if let Some(offset) = offset {
+            parameters.insert("offset", offset.to_string());
+        }
+
+        if let Some(limit) = limit {
+            parameters.insert("limit", limit.to_string());
+        }
+
+        if let Some(sort_by) = sort_by {
+            parameters.insert("sort_by", sort_by);
+        }
+
+        if let Some(sort_direction) = sort_direction {
+            parameters.insert("sort_direction", sort_direction);
+        }
+
+        if let Some(force_clean) = force_clean {
+            parameters.insert("force_clean", force_clean.to_string());
+        }
+
+        self.get("SYNO.FileStation.Sharing", "list", 3, &parameters).await
+    }
+
     #[allow(clippy::too_many_arguments)] // Allow this so that we better match the Synology API.
     #[tracing::instrument]
     pub async fn upload<TProgressReporter: ProgressReporter + 'static>(&self,
@@ -471,14 +1470,45 @@ impl SynologyFileStation {
         crtime: Option<u64>,
         atime: Option<u64>,
         progress_reporter: Option<TProgressReporter>
-    ) -> Result<(), SynologyErrorStatus> {
-        match &self.sid {
+    ) -> Result<String, SynologyErrorStatus> {
+        let progress_reporter = SharedProgressReporter::new(progress_reporter);
+        let retrying = AtomicBool::new(false);
+
+        // Every attempt sends the whole file again. An earlier attempt may have stored the file before its
+        // connection dropped, so retries overwrite it rather than fail because it exists.
+        self.with_retry(|| self.upload_once(
+            source_file_path,
+            total_bytes,
+            target_directory_path,
+            create_parents,
+            overwrite || retrying.swap(true, Ordering::SeqCst),
+            mtime,
+            crtime,
+            atime,
+            progress_reporter.clone())).await
+    }
+
+    #[allow(clippy::too_many_arguments)] // Allow this so that we better match the Synology API.
+    #[tracing::instrument]
+    async fn upload_once<TProgressReporter: ProgressReporter + 'static>(&self,
+        source_file_path: &Path,
+        total_bytes: usize,
+        target_directory_path: &str,
+        create_parents: bool,
+        overwrite: bool,
+        mtime: Option<u64>,
+        crtime: Option<u64>,
+        atime: Option<u64>,
+        progress_reporter: SharedProgressReporter<TProgressReporter>
+    ) -> Result<String, SynologyErrorStatus> {
+        match self.sid() {
             Some(sid) => {
+                let (api_url, version) = self.resolve_api("SYNO.FileStation.Upload", 2)?;
                 let url = format!(
-                    "{}/webapi/entry.cgi?api={}&version={}&method={}&_sid={}",
-                    self.url,
+                    "{}?api={}&version={}&method={}&_sid={}",
+                    api_url,
                     "SYNO.FileStation.Upload",
-                    2,
+                    version,
                     "upload",
                     sid
                 );
@@ -492,21 +1522,52 @@ impl SynologyFileStation {
                     None => Err(SynologyErrorStatus::UnknownError)
                 }?;
 
-                let file_path_string = match source_file_path.as_os_str().to_str() {
-                    Some(file_path_string) => Ok(file_path_string),
-                    None => Err(SynologyErrorStatus::UnknownError)
-                }?;
+                let source_file = fs::File::open(source_file_path).await?;
+                let source_file_len = source_file.metadata().await?.len();
+
+                // Hash the bytes as they are sent so that callers can verify the upload.
+                let hasher = Arc::new(Mutex::new(Md5::new()));
+                let stream_hasher = hasher.clone();
+                let last_progress = Arc::new(Mutex::new(Instant::now()));
+                let stream_last_progress = last_progress.clone();
+                let body_sent = Arc::new(AtomicBool::new(source_file_len == 0));
+                let stream_body_sent = body_sent.clone();
+                let mut bytes_sent: u64 = 0;
+
+                let source_stream = ReaderStream::new(source_file)
+                    .inspect_ok(move |chunk| {
+                        if let Ok(mut hasher) = stream_hasher.lock() {
+                            hasher.update(chunk);
+                        }
+
+                        if let Ok(mut last_progress) = stream_last_progress.lock() {
+                            *last_progress = Instant::now();
+                        }
+
+                        bytes_sent += chunk.len() as u64;
+                        if bytes_sent >= source_file_len {
+                            stream_body_sent.store(true, Ordering::SeqCst);
+                        }
+
+                        // Scale the progress to the size git lfs expects, since the file we send may be compressed.
+                        let bytes_so_far = if source_file_len == 0 {
+                            total_bytes
+                        }
+                        else {
+                            (bytes_sent as u128 * total_bytes as u128 / source_file_len as u128) as usize
+                        };
 
-                let part = reqwest::multipart::Part::file(file_path_string)
-                    .await?
+                        progress_reporter.set_total(bytes_so_far);
+                    });
+
+                let part = reqwest::multipart::Part::stream_with_length(Body::wrap_stream(source_stream), source_file_len)
                     .file_name(source_file_name)
                     .mime_str("application/octet-stream")?;
 
                 let form = reqwest::multipart::Form::new()
                     .text("path", target_directory_path.to_string())
                     .text("create_parents", create_parents.to_string())
-                    .text("overwrite", overwrite.to_string())
-                    .part("files", part);
+                    .text("overwrite", overwrite.to_string());
 
                 let form = if let Some(mtime) = mtime {
                     form.text("mtime", mtime.to_string())
@@ -529,24 +1590,32 @@ impl SynologyFileStation {
                     form
                 };
 
-                let response = reqwest::Client::new()
-                    .post(url)
-                    .timeout(Duration::from_secs(30))
-                    .multipart(form)
-                    .send()
-                    .await;
-                let _ = self.parse::<crate::synology_api::responses::Empty>(response).await?;
+                // The file must be the last part of the form.
+                let form = form.part("files", part);
 
-                if let Some(mut progress_reporter) = progress_reporter {
-                    info!("Reporting complete progress.");
-                    let result = progress_reporter.update(total_bytes);
+                // A whole request timeout would fail large uploads, so only fail if the upload stops making progress.
+                let response = tokio::select! {
+                    response = self.client.post(url).multipart(form).send() => response,
+                    _ = self.wait_for_idle(&last_progress, &body_sent) => {
+                        return Err(SynologyErrorStatus::Timeout);
+                    }
+                };
+                match timeout(self.options.request_timeout, self.parse::<crate::synology_api::responses::Empty>(response)).await {
+                    Ok(result) => result?,
+                    Err(_) => {
+                        warn!("Upload response was not read within {} seconds.", self.options.request_timeout.as_secs());
 
-                    if let Err(error) = result {
-                        warn!("An error occurred reporting progress: \"{error}\".");
+                        return Err(SynologyErrorStatus::Timeout);
                     }
-                }
+                };
 
-                Ok(())
+                let md5 = match hasher.lock() {
+                    Ok(hasher) => Ok(format!("{:x}", hasher.clone().finalize())),
+                    Err(_) => Err(SynologyErrorStatus::UnknownError)
+                }?;
+                info!("Uploaded file MD5 is \"{}\".", md5);
+
+                Ok(md5)
             },
             None => Err(SynologyErrorStatus::NotLoggedIn)
         }
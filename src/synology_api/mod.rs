@@ -1,7 +1,16 @@
+mod certificate;
 mod file_station;
+mod options;
+mod partial_download;
 mod progress_reporter;
 mod responses;
+mod retry;
+mod totp;
 
+pub use certificate::{display_fingerprint, normalize_fingerprint};
 pub use file_station::SynologyFileStation;
+pub use options::{AdditionalInfo, ClientOptions, ConflictPolicy, SearchCriteria, SharingLinkOptions};
 pub use progress_reporter::ProgressReporter;
-pub use responses::SynologyErrorStatus;
\ No newline at end of file
+pub use responses::{SharedFolder, SynologyErrorStatus, SynologyStatusCode};
+pub use retry::RetryPolicy;
+pub use totp::decode_totp_secret;
\ No newline at end of file
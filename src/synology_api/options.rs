@@ -0,0 +1,186 @@
+use std::{path::PathBuf, time::Duration};
+
+use educe::Educe;
+
+use super::RetryPolicy;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ConflictPolicy {
+    /// Return an error if the destination already exists.
+    Error,
+    /// Overwrite the destination if it already exists.
+    Overwrite,
+    /// Skip the source if the destination already exists.
+    Skip
+}
+
+impl ConflictPolicy {
+    pub fn overwrite_parameter(&self) -> Option<String> {
+        match self {
+            ConflictPolicy::Error => None,
+            ConflictPolicy::Overwrite => Some(true.to_string()),
+            ConflictPolicy::Skip => Some(false.to_string())
+        }
+    }
+}
+
+/// Settings for the HTTP connections made to the NAS.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ClientOptions {
+    /// How long to wait for a connection to be established.
+    pub connect_timeout: Duration,
+    /// How long a transfer can go without making progress before it is abandoned.
+    pub idle_timeout: Duration,
+    /// How many connections are used to download objects of at least `parallel_download_threshold` bytes.
+    pub download_connections: usize,
+    /// The size in bytes from which objects are downloaded over several connections.
+    pub parallel_download_threshold: u64,
+    /// How long an API request, other than an upload or download, can take in total.
+    pub request_timeout: Duration,
+    /// How long the NAS can take to respond once the body of an upload has been sent.
+    pub response_timeout: Duration,
+    /// A proxy for all requests. When not set, the proxy environment variables are used.
+    pub proxy: Option<String>,
+    /// Whether the certificate presented by the NAS is verified.
+    pub verify_certificate: bool,
+    /// A PEM bundle of additional CA certificates to trust.
+    pub ca_bundle: Option<PathBuf>,
+    /// The SHA-256 fingerprint of the only certificate the NAS may present.
+    pub certificate_fingerprint: Option<String>,
+    /// How requests that fail with a transient error are retried.
+    pub retry: RetryPolicy
+}
+
+impl Default for ClientOptions {
+    fn default() -> ClientOptions {
+        ClientOptions {
+            connect_timeout: Duration::from_secs(30),
+            idle_timeout: Duration::from_secs(300),
+            download_connections: 4,
+            parallel_download_threshold: 256 * 1024 * 1024,
+            request_timeout: Duration::from_secs(60),
+            response_timeout: Duration::from_secs(30 * 60),
+            proxy: None,
+            verify_certificate: true,
+            ca_bundle: None,
+            certificate_fingerprint: None,
+            retry: RetryPolicy::default()
+        }
+    }
+}
+
+/// The `additional` fields requested from the List APIs.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct AdditionalInfo {
+    real_path: bool,
+    size: bool,
+    owner: bool,
+    time: bool,
+    perm: bool,
+    mount_point_type: bool,
+    file_type: bool,
+    volume_status: bool
+}
+
+#[allow(dead_code)] // Not all fields are requested by the subcommands yet.
+impl AdditionalInfo {
+    pub fn new() -> AdditionalInfo {
+        AdditionalInfo::default()
+    }
+
+    pub fn real_path(mut self) -> AdditionalInfo {
+        self.real_path = true;
+        self
+    }
+
+    pub fn size(mut self) -> AdditionalInfo {
+        self.size = true;
+        self
+    }
+
+    pub fn owner(mut self) -> AdditionalInfo {
+        self.owner = true;
+        self
+    }
+
+    pub fn time(mut self) -> AdditionalInfo {
+        self.time = true;
+        self
+    }
+
+    pub fn perm(mut self) -> AdditionalInfo {
+        self.perm = true;
+        self
+    }
+
+    pub fn mount_point_type(mut self) -> AdditionalInfo {
+        self.mount_point_type = true;
+        self
+    }
+
+    /// Only supported by `list` and `get_info`.
+    pub fn file_type(mut self) -> AdditionalInfo {
+        self.file_type = true;
+        self
+    }
+
+    /// Only supported by `list_share`.
+    pub fn volume_status(mut self) -> AdditionalInfo {
+        self.volume_status = true;
+        self
+    }
+
+    pub fn to_parameter(self) -> Option<String> {
+        let fields = [
+            (self.real_path, "real_path"),
+            (self.size, "size"),
+            (self.owner, "owner"),
+            (self.time, "time"),
+            (self.perm, "perm"),
+            (self.mount_point_type, "mount_point_type"),
+            (self.file_type, "type"),
+            (self.volume_status, "volume_status")
+        ];
+
+        let additional = fields
+            .iter()
+            .filter(|(include, _)| *include)
+            .map(|(_, name)| *name)
+            .collect::<Vec<&str>>();
+
+        if additional.is_empty() {
+            None
+        }
+        else {
+            Some(additional.join(","))
+        }
+    }
+}
+
+/// The criteria used to start a search task.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SearchCriteria {
+    pub folder_paths: Vec<String>,
+    pub recursive: bool,
+    pub pattern: Option<String>,
+    pub extension: Option<String>,
+    pub file_type: Option<String>,
+    pub size_from: Option<u64>,
+    pub size_to: Option<u64>,
+    pub mtime_from: Option<u64>,
+    pub mtime_to: Option<u64>
+}
+
+/// The options used to create a sharing link.
+#[derive(Clone, Default, Educe)]
+#[educe(Debug)]
+pub struct SharingLinkOptions {
+    #[educe(Debug(ignore))] // Do not include password in logs.
+    pub password: Option<String>,
+    /// The date the link expires, formatted as YYYY-MM-DD.
+    pub date_expired: Option<String>,
+    /// The date the link becomes available, formatted as YYYY-MM-DD.
+    pub date_available: Option<String>,
+    /// The number of times the link can be accessed.
+    pub expire_times: Option<u32>
+}
@@ -0,0 +1,372 @@
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use std::io::SeekFrom;
+
+use tokio::{fs::{self, File, OpenOptions}, io::AsyncSeekExt, sync::Mutex};
+use tracing::info;
+
+use super::SynologyErrorStatus;
+
+/// Identifies the remote object a partial download belongs to.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde[rename_all = "snake_case"]]
+pub struct DownloadState {
+    pub size: u64,
+    pub mtime: u64
+}
+
+/// The part of the staging file a connection of a parallel download is responsible for.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde[rename_all = "snake_case"]]
+pub struct SegmentState {
+    pub start: u64,
+    pub end: u64,
+    pub downloaded: u64
+}
+
+impl SegmentState {
+    pub fn offset(&self) -> u64 {
+        self.start + self.downloaded
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.offset() > self.end
+    }
+}
+
+/// The sidecar contents. Parallel downloads also record how far each segment got, as their staging file is
+/// allocated up front and its length says nothing about what was downloaded.
+#[derive(Debug, Serialize, Deserialize)]
+struct Sidecar {
+    #[serde(flatten)]
+    state: DownloadState,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    segments: Option<Vec<SegmentState>>
+}
+
+/// A download kept in a staging file until it is complete, with a sidecar describing the remote object.
+#[derive(Debug)]
+pub struct PartialDownload {
+    staging_path: PathBuf,
+    state_path: PathBuf,
+    sidecar: Mutex<Option<Sidecar>>
+}
+
+impl PartialDownload {
+    #[tracing::instrument]
+    pub fn new(target_file_path: &Path) -> PartialDownload {
+        let mut staging_path = target_file_path.as_os_str().to_owned();
+        staging_path.push(".part");
+
+        let mut state_path = staging_path.clone();
+        state_path.push(".json");
+
+        PartialDownload {
+            staging_path: PathBuf::from(staging_path),
+            state_path: PathBuf::from(state_path),
+            sidecar: Mutex::new(None)
+        }
+    }
+
+    #[tracing::instrument]
+    async fn read_sidecar(&self) -> Option<Sidecar> {
+        let state_json = fs::read_to_string(&self.state_path).await.ok()?;
+
+        serde_json::from_str::<Sidecar>(&state_json).ok()
+    }
+
+    #[tracing::instrument]
+    async fn write_sidecar(&self, sidecar: &Sidecar) -> Result<(), SynologyErrorStatus> {
+        fs::write(&self.state_path, serde_json::to_string(sidecar)?).await?;
+
+        Ok(())
+    }
+
+    /// Whether a single connection download of the same remote object was interrupted and can be resumed.
+    #[tracing::instrument]
+    pub async fn has_resumable_stream(&self, state: &DownloadState) -> bool {
+        match self.read_sidecar().await {
+            Some(sidecar) if &sidecar.state == state && sidecar.segments.is_none() =>
+                fs::metadata(&self.staging_path).await.is_ok_and(|metadata| metadata.len() > 0),
+            _ => false
+        }
+    }
+
+    #[tracing::instrument]
+    pub async fn resume_offset(&self, state: &DownloadState) -> Result<u64, SynologyErrorStatus> {
+        if let Some(sidecar) = self.read_sidecar().await {
+            // The length of a parallel download's staging file can't be resumed from.
+            if &sidecar.state == state && sidecar.segments.is_none() {
+                if let Ok(metadata) = fs::metadata(&self.staging_path).await {
+                    if metadata.len() <= state.size {
+                        info!("Resuming download from byte {}.", metadata.len());
+
+                        return Ok(metadata.len());
+                    }
+                }
+            }
+            else {
+                info!("Remote object changed since the partial download was started.");
+            }
+        }
+
+        // Start over, recording which remote object the staging file is for.
+        self.restart(state).await?;
+
+        Ok(0)
+    }
+
+    #[tracing::instrument]
+    pub async fn restart(&self, state: &DownloadState) -> Result<(), SynologyErrorStatus> {
+        if fs::try_exists(&self.staging_path).await? {
+            fs::remove_file(&self.staging_path).await?;
+        }
+
+        self.write_sidecar(&Sidecar { state: state.clone(), segments: None }).await
+    }
+
+    /// Returns the progress of each segment, continuing an interrupted parallel download of the same remote object
+    /// over the same segments or else allocating a new staging file.
+    #[tracing::instrument]
+    pub async fn resume_segments(&self, state: &DownloadState, ranges: &[(u64, u64)]) -> Result<Vec<SegmentState>, SynologyErrorStatus> {
+        let mut current = self.sidecar.lock().await;
+
+        if let Some(sidecar) = self.read_sidecar().await {
+            let staging_len = fs::metadata(&self.staging_path).await.map(|metadata| metadata.len()).ok();
+
+            if let Some(segments) = &sidecar.segments {
+                let same_ranges = segments.iter().map(|segment| (segment.start, segment.end)).eq(ranges.iter().copied());
+
+                if &sidecar.state == state && same_ranges && staging_len == Some(state.size) {
+                    info!("Resuming parallel download.");
+
+                    let segments = segments.clone();
+                    *current = Some(sidecar);
+
+                    return Ok(segments);
+                }
+            }
+        }
+
+        // The segments write into their own region of the staging file, so it is allocated up front.
+        self.discard().await?;
+
+        let file = self.open(false).await?;
+        file.set_len(state.size).await?;
+
+        let segments = ranges.iter()
+            .map(|(start, end)| SegmentState { start: *start, end: *end, downloaded: 0 })
+            .collect::<Vec<SegmentState>>();
+        let sidecar = Sidecar { state: state.clone(), segments: Some(segments.clone()) };
+        self.write_sidecar(&sidecar).await?;
+        *current = Some(sidecar);
+
+        Ok(segments)
+    }
+
+    /// Records how many bytes of a segment are in the staging file.
+    #[tracing::instrument]
+    pub async fn record_segment(&self, index: usize, downloaded: u64) -> Result<(), SynologyErrorStatus> {
+        let mut current = self.sidecar.lock().await;
+
+        let sidecar = current.as_mut().ok_or(SynologyErrorStatus::UnknownError)?;
+        let segment = sidecar.segments.as_mut()
+            .and_then(|segments| segments.get_mut(index))
+            .ok_or(SynologyErrorStatus::UnknownError)?;
+        segment.downloaded = downloaded;
+
+        // Holding the lock while writing keeps concurrent segments from interleaving their writes.
+        self.write_sidecar(sidecar).await
+    }
+
+    #[tracing::instrument]
+    pub async fn open(&self, append: bool) -> Result<File, SynologyErrorStatus> {
+        Ok(
+            OpenOptions::new()
+                .create(true)
+                .write(true)
+                .append(append)
+                .truncate(!append)
+                .open(&self.staging_path)
+                .await?
+        )
+    }
+
+    /// Removes the staging file and sidecar so that nothing is resumed from them.
+    #[tracing::instrument]
+    pub async fn discard(&self) -> Result<(), SynologyErrorStatus> {
+        for path in [&self.staging_path, &self.state_path] {
+            if fs::try_exists(path).await? {
+                fs::remove_file(path).await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    #[tracing::instrument]
+    pub async fn open_at(&self, offset: u64) -> Result<File, SynologyErrorStatus> {
+        let mut file = OpenOptions::new()
+            .write(true)
+            .open(&self.staging_path)
+            .await?;
+        file.seek(SeekFrom::Start(offset)).await?;
+
+        Ok(file)
+    }
+
+    #[tracing::instrument]
+    pub async fn finish(&self, target_file_path: &Path) -> Result<(), SynologyErrorStatus> {
+        fs::rename(&self.staging_path, target_file_path).await?;
+
+        if fs::try_exists(&self.state_path).await? {
+            fs::remove_file(&self.state_path).await?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs, path::PathBuf};
+
+    use super::{DownloadState, PartialDownload};
+
+    const STATE: DownloadState = DownloadState { size: 10, mtime: 1000 };
+
+    fn create_target_path(name: &str) -> PathBuf {
+        let directory = env::temp_dir().join(format!("git-lfs-synology-{}-{}", std::process::id(), name));
+        fs::create_dir_all(&directory).unwrap();
+
+        directory.join("object")
+    }
+
+    async fn stage(partial_download: &PartialDownload, state: &DownloadState, contents: &[u8]) {
+        partial_download.restart(state).await.unwrap();
+        fs::write(&partial_download.staging_path, contents).unwrap();
+    }
+
+    #[tokio::test]
+    async fn resume_offset_matching_state() {
+        let partial_download = PartialDownload::new(&create_target_path("matching_state"));
+        stage(&partial_download, &STATE, b"12345").await;
+
+        assert_eq!(partial_download.resume_offset(&STATE).await.unwrap(), 5);
+        assert_eq!(fs::read(&partial_download.staging_path).unwrap(), b"12345");
+    }
+
+    #[tokio::test]
+    async fn resume_offset_without_staging_file() {
+        let partial_download = PartialDownload::new(&create_target_path("without_staging_file"));
+        partial_download.discard().await.unwrap();
+
+        assert_eq!(partial_download.resume_offset(&STATE).await.unwrap(), 0);
+        assert!(partial_download.state_path.exists());
+    }
+
+    #[tokio::test]
+    async fn resume_offset_size_changed() {
+        let partial_download = PartialDownload::new(&create_target_path("size_changed"));
+        stage(&partial_download, &STATE, b"12345").await;
+
+        let state = DownloadState { size: 20, ..STATE };
+
+        assert_eq!(partial_download.resume_offset(&state).await.unwrap(), 0);
+        assert!(!partial_download.staging_path.exists());
+    }
+
+    #[tokio::test]
+    async fn resume_offset_mtime_changed() {
+        let partial_download = PartialDownload::new(&create_target_path("mtime_changed"));
+        stage(&partial_download, &STATE, b"12345").await;
+
+        let state = DownloadState { mtime: 2000, ..STATE };
+
+        assert_eq!(partial_download.resume_offset(&state).await.unwrap(), 0);
+        assert!(!partial_download.staging_path.exists());
+
+        // The sidecar now describes the new remote object.
+        fs::write(&partial_download.staging_path, b"123").unwrap();
+        assert_eq!(partial_download.resume_offset(&state).await.unwrap(), 3);
+    }
+
+    #[tokio::test]
+    async fn resume_offset_staging_file_larger_than_remote() {
+        let partial_download = PartialDownload::new(&create_target_path("larger_than_remote"));
+        stage(&partial_download, &STATE, b"0123456789ABCDEF").await;
+
+        assert_eq!(partial_download.resume_offset(&STATE).await.unwrap(), 0);
+        assert!(!partial_download.staging_path.exists());
+    }
+
+    #[tokio::test]
+    async fn resume_segments_matching_state() {
+        let partial_download = PartialDownload::new(&create_target_path("segments_matching_state"));
+        partial_download.discard().await.unwrap();
+
+        let ranges = [(0, 4), (5, 9)];
+        let segments = partial_download.resume_segments(&STATE, &ranges).await.unwrap();
+
+        assert_eq!(segments.iter().map(|segment| segment.downloaded).collect::<Vec<u64>>(), vec![0, 0]);
+        assert_eq!(fs::metadata(&partial_download.staging_path).unwrap().len(), 10);
+
+        partial_download.record_segment(1, 3).await.unwrap();
+
+        // A new process sees the recorded progress, but a single connection must not resume from the file length.
+        let partial_download = PartialDownload::new(&create_target_path("segments_matching_state"));
+        assert!(!partial_download.has_resumable_stream(&STATE).await);
+
+        let segments = partial_download.resume_segments(&STATE, &ranges).await.unwrap();
+
+        assert_eq!(segments[1].downloaded, 3);
+        assert_eq!(segments[1].offset(), 8);
+        assert!(!segments[1].is_complete());
+    }
+
+    #[tokio::test]
+    async fn resume_segments_changed() {
+        let partial_download = PartialDownload::new(&create_target_path("segments_changed"));
+        partial_download.discard().await.unwrap();
+
+        partial_download.resume_segments(&STATE, &[(0, 4), (5, 9)]).await.unwrap();
+        partial_download.record_segment(0, 5).await.unwrap();
+
+        // Different segments, such as after changing the number of connections, start over.
+        let segments = partial_download.resume_segments(&STATE, &[(0, 9)]).await.unwrap();
+        assert_eq!(segments[0].downloaded, 0);
+
+        partial_download.record_segment(0, 5).await.unwrap();
+
+        let state = DownloadState { mtime: 2000, ..STATE };
+        let segments = partial_download.resume_segments(&state, &[(0, 9)]).await.unwrap();
+        assert_eq!(segments[0].downloaded, 0);
+    }
+
+    #[tokio::test]
+    async fn has_resumable_stream() {
+        let partial_download = PartialDownload::new(&create_target_path("resumable_stream"));
+        partial_download.discard().await.unwrap();
+
+        assert!(!partial_download.has_resumable_stream(&STATE).await);
+
+        stage(&partial_download, &STATE, b"12345").await;
+
+        assert!(partial_download.has_resumable_stream(&STATE).await);
+        assert!(!partial_download.has_resumable_stream(&DownloadState { size: 20, ..STATE }).await);
+    }
+
+    #[tokio::test]
+    async fn finish_moves_staging_file() {
+        let target_path = create_target_path("finish");
+        let partial_download = PartialDownload::new(&target_path);
+        stage(&partial_download, &STATE, b"0123456789").await;
+
+        partial_download.finish(&target_path).await.unwrap();
+
+        assert_eq!(fs::read(&target_path).unwrap(), b"0123456789");
+        assert!(!partial_download.staging_path.exists());
+        assert!(!partial_download.state_path.exists());
+    }
+}
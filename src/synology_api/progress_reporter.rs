@@ -1,7 +1,70 @@
-use std::fmt::Debug;
+use std::{fmt::Debug, sync::{Arc, Mutex}};
 
 use anyhow::Result;
+use tracing::warn;
 
 pub trait ProgressReporter: Send + Debug {
     fn update(&mut self, bytes_since_last: usize) -> Result<()>;
-}
\ No newline at end of file
+}
+
+#[derive(Debug)]
+struct ProgressState<TProgressReporter: ProgressReporter> {
+    progress_reporter: Option<TProgressReporter>,
+    bytes_reported: usize
+}
+
+/// Shares a progress reporter between concurrent connections and retried attempts without reporting any byte twice.
+#[derive(Debug)]
+pub struct SharedProgressReporter<TProgressReporter: ProgressReporter> {
+    state: Arc<Mutex<ProgressState<TProgressReporter>>>
+}
+
+impl<TProgressReporter: ProgressReporter> Clone for SharedProgressReporter<TProgressReporter> {
+    fn clone(&self) -> Self {
+        SharedProgressReporter {
+            state: self.state.clone()
+        }
+    }
+}
+
+impl<TProgressReporter: ProgressReporter> SharedProgressReporter<TProgressReporter> {
+    pub fn new(progress_reporter: Option<TProgressReporter>) -> SharedProgressReporter<TProgressReporter> {
+        SharedProgressReporter {
+            state: Arc::new(Mutex::new(ProgressState {
+                progress_reporter,
+                bytes_reported: 0
+            }))
+        }
+    }
+
+    /// Reports bytes transferred in addition to those already reported.
+    pub fn add(&self, bytes_since_last: usize) {
+        if let Ok(mut state) = self.state.lock() {
+            state.bytes_reported += bytes_since_last;
+
+            if let Some(progress_reporter) = state.progress_reporter.as_mut() {
+                if let Err(error) = progress_reporter.update(bytes_since_last) {
+                    warn!("An error occurred reporting progress: \"{error}\".");
+                }
+            }
+        }
+    }
+
+    /// Reports the total bytes transferred so far, ignoring any that were already reported by an earlier attempt.
+    pub fn set_total(&self, bytes_so_far: usize) {
+        if let Ok(mut state) = self.state.lock() {
+            if bytes_so_far <= state.bytes_reported {
+                return;
+            }
+
+            let bytes_since_last = bytes_so_far - state.bytes_reported;
+            state.bytes_reported = bytes_so_far;
+
+            if let Some(progress_reporter) = state.progress_reporter.as_mut() {
+                if let Err(error) = progress_reporter.update(bytes_since_last) {
+                    warn!("An error occurred reporting progress: \"{error}\".");
+                }
+            }
+        }
+    }
+}
@@ -70,6 +70,30 @@ pub enum SynologyStatusCode {
     IllegalFileNameOnFatFileSystem = 420,
     #[error("Device or resource busy")]
     DeviceOrResourceBusy = 421,
+    #[error("Failed to delete file(s)/folder(s)")]
+    FailedToDeleteFilesFolders = 900,
+    #[error("Failed to copy files/folders")]
+    FailedToCopyFilesFolders = 1000,
+    #[error("Failed to move files/folders")]
+    FailedToMoveFilesFolders = 1001,
+    #[error("An error occurred at the destination")]
+    ErrorOccurredAtTheDestination = 1002,
+    #[error("Cannot overwrite or skip the existing file because no overwrite parameter is given")]
+    CannotOverwriteOrSkipTheExistingFile = 1003,
+    #[error("File cannot overwrite a folder with the same name, or folder cannot overwrite a file with the same name")]
+    FileCannotOverwriteAFolderWithTheSameName = 1004,
+    #[error("Cannot copy/move file/folder with special characters to a FAT32 file system")]
+    CannotCopyMoveWithSpecialCharactersToFat32 = 1006,
+    #[error("Cannot copy/move a file bigger than 4G to a FAT32 file system")]
+    CannotCopyMoveFileBiggerThan4GToFat32 = 1007,
+    #[error("Failed to rename it")]
+    FailedToRenameIt = 1200,
+    #[error("Sharing link does not exist")]
+    SharingLinkDoesNotExist = 2000,
+    #[error("Cannot generate sharing link because too many sharing links exist")]
+    TooManySharingLinksExist = 2001,
+    #[error("Failed to access sharing links")]
+    FailedToAccessSharingLinks = 2002,
     #[error("No such task of the file operation")]
     NoSuchTaskOfTheFileOperation = 599
 }
@@ -88,8 +112,20 @@ pub enum SynologyErrorStatus {
     IoError(#[from] std::io::Error),
     #[error("TOTP required but not provided")]
     NoTotp,
+    #[error("The TOTP secret is not valid base32.")]
+    InvalidTotpSecret,
     #[error("No user logged in")]
     NotLoggedIn,
+    #[error("The API \"{0}\" is not supported by the server.")]
+    UnsupportedApi(String),
+    #[error("The request timed out.")]
+    Timeout,
+    #[error("\"{0}\" is not a SHA-256 certificate fingerprint.")]
+    InvalidCertificateFingerprint(String),
+    #[error("TLS could not be configured: {0}")]
+    TlsError(String),
+    #[error("The object changed on the server during the download.")]
+    ObjectChanged,
     #[error("An unknown error occurred.")]
     UnknownError
 }
@@ -113,6 +149,59 @@ pub struct SynologyError<TErrors> {
     pub errors: Option<TErrors>
 }
 
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde[rename_all = "camelCase"]]
+pub struct ApiInfo {
+    pub path: String,
+    pub min_version: u32,
+    pub max_version: u32,
+    pub request_format: Option<String>
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde[rename_all = "snake_case"]]
+pub struct TaskResponse {
+    pub taskid: String
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde[rename_all = "snake_case"]]
+pub struct DeleteStatusResponse {
+    pub finished: bool,
+    pub path: Option<String>,
+    pub processed_num: Option<i64>,
+    pub processing_path: Option<String>,
+    pub progress: Option<f64>,
+    pub total: Option<i64>
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde[rename_all = "snake_case"]]
+pub struct CopyMoveStatusResponse {
+    pub finished: bool,
+    pub dest_folder_path: Option<String>,
+    pub path: Option<String>,
+    pub processed_size: Option<u64>,
+    pub progress: Option<f64>,
+    pub total: Option<i64>
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde[rename_all = "snake_case"]]
+pub struct DirSizeStatusResponse {
+    pub finished: bool,
+    pub num_dir: u64,
+    pub num_file: u64,
+    pub total_size: u64
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde[rename_all = "snake_case"]]
+pub struct Md5StatusResponse {
+    pub finished: bool,
+    pub md5: Option<String>
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 #[serde[rename_all = "snake_case"]]
 pub struct LoginResponse {
@@ -149,21 +238,60 @@ pub struct ListResponse {
     pub files: Vec<File>
 }
 
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde[rename_all = "snake_case"]]
+pub struct GetInfoResponse {
+    pub files: Vec<FileInfo>
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde[rename_all = "snake_case"]]
+pub struct FileInfo {
+    pub path: String,
+    pub name: String,
+    pub isdir: Option<bool>,
+    pub code: Option<u32>,
+    pub additional: Option<FileAdditional>
+}
+
+impl FileInfo {
+    pub fn exists(&self) -> bool {
+        // The server reports missing paths with an error code instead of failing the request.
+        self.code.is_none()
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde[rename_all = "snake_case"]]
+pub struct RenameResponse {
+    pub files: Vec<File>
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde[rename_all = "snake_case"]]
+pub struct SearchListResponse {
+    pub total: u64,
+    pub offset: u64,
+    pub finished: bool,
+    pub files: Vec<File>
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 #[serde[rename_all = "snake_case"]]
 pub struct File {
     pub path: String,
     pub name: String,
     pub isdir: bool,
-    pub children: Option<FileChildren>
+    pub children: Option<FileChildren>,
+    pub additional: Option<FileAdditional>
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 #[serde[rename_all = "snake_case"]]
 pub struct FileChildren {
-    total: u32,
-    offeset: i32,
-    files: Vec<File>
+    pub total: u64,
+    pub offset: u64,
+    pub files: Vec<File>
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -174,7 +302,7 @@ pub struct FileAdditional {
     pub owner: Option<Owner>,
     pub time: Option<Time>,
     pub perm: Option<FilePerm>,
-    pub mount_point_time: Option<String>,
+    pub mount_point_type: Option<String>,
     #[serde(alias = "type")]
     pub extension: Option<String>
 }
@@ -269,4 +397,44 @@ pub struct VolumeStatus {
     pub freespace: u64,
     pub totalspace: u64,
     pub readonly: bool
-}
\ No newline at end of file
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde[rename_all = "snake_case"]]
+pub struct CreateSharingLinkResponse {
+    pub links: Vec<CreatedSharingLink>
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde[rename_all = "snake_case"]]
+pub struct CreatedSharingLink {
+    pub id: String,
+    pub url: String,
+    pub path: String,
+    pub qrcode: Option<String>,
+    pub error: Option<u32>
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde[rename_all = "snake_case"]]
+pub struct ListSharingLinkResponse {
+    pub total: u64,
+    pub offset: u64,
+    pub links: Vec<SharingLink>
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde[rename_all = "snake_case"]]
+pub struct SharingLink {
+    pub id: String,
+    pub url: String,
+    pub path: String,
+    pub name: Option<String>,
+    pub link_owner: Option<String>,
+    #[serde(rename = "isFolder")]
+    pub is_folder: Option<bool>,
+    pub has_password: Option<bool>,
+    pub date_expired: Option<String>,
+    pub date_available: Option<String>,
+    pub status: Option<String>
+}
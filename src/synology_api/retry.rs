@@ -0,0 +1,182 @@
+use std::{io, time::Duration};
+
+use rand::Rng;
+use reqwest::StatusCode;
+
+use super::{SynologyErrorStatus, SynologyStatusCode};
+
+/// What to do after a request fails.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RetryAction {
+    /// The error will not go away by trying again.
+    Fail,
+    /// The session was lost, so log in again before retrying.
+    Relogin,
+    /// The error is transient, so wait and retry.
+    Retry
+}
+
+/// How failed requests are retried.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// The most times a request is attempted, including the first attempt.
+    pub max_attempts: u32,
+    /// The longest a request is retried for, measured from the first attempt.
+    pub deadline: Duration,
+    /// The delay before the first retry, which doubles with each further retry.
+    pub initial_backoff: Duration,
+    /// The longest delay between two attempts.
+    pub max_backoff: Duration
+}
+
+impl Default for RetryPolicy {
+    fn default() -> RetryPolicy {
+        RetryPolicy {
+            max_attempts: 5,
+            deadline: Duration::from_secs(300),
+            initial_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(30)
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Returns a random delay of up to the exponential backoff for the attempt that just failed.
+    #[tracing::instrument]
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        // Full jitter keeps agents that failed together from retrying together.
+        let backoff = self.initial_backoff
+            .saturating_mul(2u32.saturating_pow(attempt.saturating_sub(1)))
+            .min(self.max_backoff);
+
+        backoff.mul_f64(rand::thread_rng().gen_range(0.0..=1.0))
+    }
+}
+
+#[tracing::instrument]
+pub fn retry_action(error: &SynologyErrorStatus) -> RetryAction {
+    match error {
+        SynologyErrorStatus::ServerError(SynologyStatusCode::SystemIsTooBusy) => RetryAction::Retry,
+        SynologyErrorStatus::ServerError(
+            SynologyStatusCode::SessionTimeout |
+            SynologyStatusCode::SessionInterruptedByDuplicateLogin |
+            SynologyStatusCode::SidNotFound) => RetryAction::Relogin,
+        // A NAS waking from hibernation may answer through its web server before DSM is ready.
+        SynologyErrorStatus::HttpError(
+            StatusCode::BAD_GATEWAY |
+            StatusCode::SERVICE_UNAVAILABLE |
+            StatusCode::GATEWAY_TIMEOUT) => RetryAction::Retry,
+        SynologyErrorStatus::ReqwestError(error) => {
+            if error.is_connect() || error.is_timeout() || error.is_request() || error.is_body() {
+                RetryAction::Retry
+            }
+            else {
+                RetryAction::Fail
+            }
+        },
+        SynologyErrorStatus::IoError(error) => match error.kind() {
+            io::ErrorKind::ConnectionReset |
+            io::ErrorKind::ConnectionAborted |
+            io::ErrorKind::BrokenPipe |
+            io::ErrorKind::UnexpectedEof |
+            io::ErrorKind::TimedOut => RetryAction::Retry,
+            _ => RetryAction::Fail
+        },
+        SynologyErrorStatus::Timeout => RetryAction::Retry,
+        _ => RetryAction::Fail
+    }
+}
+
+/// For requests that must not be carried out twice, such as starting a task, only retries errors that show the
+/// request never reached DSM or was refused by it.
+#[tracing::instrument]
+pub fn non_idempotent_retry_action(error: &SynologyErrorStatus) -> RetryAction {
+    match error {
+        SynologyErrorStatus::ReqwestError(error) if error.is_connect() => RetryAction::Retry,
+        SynologyErrorStatus::ServerError(SynologyStatusCode::SystemIsTooBusy) => RetryAction::Retry,
+        _ => match retry_action(error) {
+            RetryAction::Retry => RetryAction::Fail,
+            action => action
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{io, time::Duration};
+
+    use reqwest::StatusCode;
+
+    use super::{non_idempotent_retry_action, retry_action, RetryAction, RetryPolicy};
+    use crate::synology_api::{SynologyErrorStatus, SynologyStatusCode};
+
+    #[test]
+    fn retry_action_server_errors() {
+        assert_eq!(retry_action(&SynologyErrorStatus::ServerError(SynologyStatusCode::SystemIsTooBusy)), RetryAction::Retry);
+        assert_eq!(retry_action(&SynologyErrorStatus::ServerError(SynologyStatusCode::SessionTimeout)), RetryAction::Relogin);
+        assert_eq!(retry_action(&SynologyErrorStatus::ServerError(SynologyStatusCode::SidNotFound)), RetryAction::Relogin);
+        assert_eq!(retry_action(&SynologyErrorStatus::ServerError(SynologyStatusCode::NoSuchFileOrDirectory)), RetryAction::Fail);
+    }
+
+    #[test]
+    fn retry_action_http_errors() {
+        assert_eq!(retry_action(&SynologyErrorStatus::HttpError(StatusCode::SERVICE_UNAVAILABLE)), RetryAction::Retry);
+        assert_eq!(retry_action(&SynologyErrorStatus::HttpError(StatusCode::BAD_GATEWAY)), RetryAction::Retry);
+        assert_eq!(retry_action(&SynologyErrorStatus::HttpError(StatusCode::NOT_FOUND)), RetryAction::Fail);
+    }
+
+    #[test]
+    fn retry_action_io_errors() {
+        assert_eq!(retry_action(&SynologyErrorStatus::IoError(io::Error::from(io::ErrorKind::ConnectionReset))), RetryAction::Retry);
+        assert_eq!(retry_action(&SynologyErrorStatus::IoError(io::Error::from(io::ErrorKind::UnexpectedEof))), RetryAction::Retry);
+        assert_eq!(retry_action(&SynologyErrorStatus::IoError(io::Error::from(io::ErrorKind::PermissionDenied))), RetryAction::Fail);
+        assert_eq!(retry_action(&SynologyErrorStatus::Timeout), RetryAction::Retry);
+        assert_eq!(retry_action(&SynologyErrorStatus::UnknownError), RetryAction::Fail);
+    }
+
+    #[tokio::test]
+    async fn retry_action_connection_refused() {
+        // Nothing listens on port 1, so the connection is refused without leaving the machine.
+        let error = reqwest::Client::new().get("http://127.0.0.1:1/").send().await.unwrap_err();
+
+        assert_eq!(retry_action(&SynologyErrorStatus::ReqwestError(error)), RetryAction::Retry);
+    }
+
+    #[tokio::test]
+    async fn non_idempotent_retry_action_only_retries_unsent_requests() {
+        let error = reqwest::Client::new().get("http://127.0.0.1:1/").send().await.unwrap_err();
+
+        assert_eq!(non_idempotent_retry_action(&SynologyErrorStatus::ReqwestError(error)), RetryAction::Retry);
+        assert_eq!(non_idempotent_retry_action(&SynologyErrorStatus::ServerError(SynologyStatusCode::SystemIsTooBusy)), RetryAction::Retry);
+        assert_eq!(non_idempotent_retry_action(&SynologyErrorStatus::ServerError(SynologyStatusCode::SessionTimeout)), RetryAction::Relogin);
+        assert_eq!(non_idempotent_retry_action(&SynologyErrorStatus::Timeout), RetryAction::Fail);
+        assert_eq!(non_idempotent_retry_action(&SynologyErrorStatus::HttpError(StatusCode::GATEWAY_TIMEOUT)), RetryAction::Fail);
+        assert_eq!(non_idempotent_retry_action(&SynologyErrorStatus::IoError(io::Error::from(io::ErrorKind::ConnectionReset))), RetryAction::Fail);
+    }
+
+    #[test]
+    fn backoff_is_capped() {
+        let policy = RetryPolicy {
+            initial_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(30),
+            ..Default::default()
+        };
+
+        for _ in 0..100 {
+            assert!(policy.backoff(1) <= Duration::from_secs(1));
+            assert!(policy.backoff(3) <= Duration::from_secs(4));
+            assert!(policy.backoff(10) <= Duration::from_secs(30));
+            assert!(policy.backoff(u32::MAX) <= Duration::from_secs(30));
+        }
+    }
+
+    #[test]
+    fn backoff_without_initial_backoff() {
+        let policy = RetryPolicy {
+            initial_backoff: Duration::ZERO,
+            ..Default::default()
+        };
+
+        assert_eq!(policy.backoff(5), Duration::ZERO);
+    }
+}